@@ -0,0 +1,114 @@
+//! Compares the single-pass `Lexer` with the regex based `split` it replaced.
+//!
+//! Run with `cargo bench --bench lexer`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use rml_mini::scanner::{Lexer, Token, TokenKind};
+
+const FIZZ_BUZZ: &str = r#"
+fn fizzbuzz(start, end) {
+    i = start;
+    while(true){
+        if (i < end) {
+        break;
+        }
+        if(i % 15 == 0){
+            print("FizzBuzz");
+        }else if(i % 3 == 0){
+            print("Fizz");
+        }else if(i % 5 == 0){
+            print("Buzz");
+        }else {
+            print(i);
+        }
+        i = i + 1;
+    }
+}
+"#;
+
+/// The scanner before the rewrite: several `Regex::new` calls per character.
+#[allow(clippy::regex_creation_in_loops)]
+fn legacy_split(text: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let text_chars: Vec<char> = text.chars().collect();
+    let mut idx: usize = 0;
+
+    for (i, c) in text_chars.iter().enumerate() {
+        if idx > i {
+            continue
+        }
+
+        let mut str: Vec<char> = Vec::new();
+
+        if text_chars[idx] == '"' {
+            str.push(*c);
+            idx += 1;
+
+            while text_chars[idx] != '"' {
+                str.push(text_chars[idx]);
+                idx += 1;
+            }
+
+            str.push('"');
+            idx += 1;
+        } else if Regex::new(r"(=|<|>|!)").unwrap().is_match(&c.to_string()) {
+            str.push(*c);
+            idx += 1;
+
+            if text_chars[idx] == '=' {
+                str.push(text_chars[idx]);
+                idx += 1;
+            }
+        } else if Regex::new(r"\d").unwrap().is_match(&c.to_string()) {
+            while idx < text_chars.len() && Regex::new(r"\d").unwrap().is_match(&text_chars[idx].to_string()) {
+                str.push(text_chars[idx]);
+                idx += 1;
+            }
+        } else if Regex::new(r"[a-z|A-Z]").unwrap().is_match(&c.to_string()) {
+            while idx < text_chars.len() && Regex::new(r"[a-z|A-Z]|[0-9]").unwrap().is_match(&text_chars[idx].to_string()) {
+                str.push(text_chars[idx]);
+                idx += 1;
+            }
+        } else if text_chars[idx] == ' ' || text_chars[idx] == '\n' {
+            idx += 1;
+            continue;
+        } else {
+            str.push(*c);
+            idx += 1;
+        }
+
+        tokens.push(Token { kind: TokenKind::Ident, value: str.iter().collect(), span: Default::default(), trivia: Vec::new() });
+    }
+
+    tokens
+}
+
+fn measure(iterations: u32, mut f: impl FnMut() -> usize) -> Duration {
+    let started = Instant::now();
+    for _ in 0..iterations {
+        black_box(f());
+    }
+    started.elapsed() / iterations
+}
+
+fn main() {
+    for copies in [1, 10, 50] {
+        let text = FIZZ_BUZZ.repeat(copies);
+        let lines = text.lines().count();
+
+        let legacy = measure(3, || legacy_split(black_box(&text)).len());
+        let lexer = measure(100, || Lexer::new(black_box(&text)).count());
+
+        assert_eq!(legacy_split(&text).len(), Lexer::new(&text).count());
+        println!(
+            "{:>5} lines: legacy split {:>12?}, lexer {:>12?} ({:.0}x faster)",
+            lines,
+            legacy,
+            lexer,
+            legacy.as_secs_f64() / lexer.as_secs_f64(),
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! Checks that run on the AST before the program does. They only warn: the
+//! program still runs, and fails at runtime if a warning was right.
+
+use std::collections::HashMap;
+
+use super::diagnostics::Diagnostic;
+use super::parser::{Arm, EnumDef, Expr, ExprKind, Item, Literal, Pattern, PatternKind, Program};
+use super::visit::{self, Visitor};
+
+/// Warns about `match` expressions that miss some values and arms that no value reaches.
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+    let enums: Vec<&EnumDef> = program.iter().filter_map(|item| match item {
+        Item::Enum(enum_def) => Some(enum_def),
+        _ => None,
+    }).collect();
+
+    let mut variants = HashMap::new();
+    for (i, enum_def) in enums.iter().enumerate() {
+        for (j, variant) in enum_def.variants.iter().enumerate() {
+            variants.insert(variant.name.name.as_str(), (i, j));
+        }
+    }
+
+    let mut checker = MatchChecker { enums, variants, diagnostics: Vec::new() };
+    checker.visit_program(program);
+    checker.diagnostics
+}
+
+/// A pattern reduced to what decides which values it matches.
+#[derive(Debug, Clone, PartialEq)]
+enum Pat {
+    /// `_` or a binding.
+    Any,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+/// What a value is made of at the top, e.g. a variant or a literal.
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    /// Variant `.1` of enum `.0`, both indices into the program's enums.
+    Variant(usize, usize),
+    Bool(bool),
+    /// A number. Floats without a fractional part are ints, as `1.0` matches
+    /// where `1` does. There are too many numbers and strings to list them all.
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+/// The constructor of a literal, keyed on its value like the `==` that
+/// matches it at run time.
+fn literal_ctor(literal: &Literal) -> Ctor {
+    match literal {
+        Literal::Int(value) => Ctor::Int(*value),
+        Literal::Float(value) if value.fract() == 0.0 && value.abs() < i64::MAX as f64 => Ctor::Int(*value as i64),
+        Literal::Float(value) => Ctor::Float(*value),
+        Literal::String(value) => Ctor::String(value.clone()),
+        Literal::Bool(value) => Ctor::Bool(*value),
+    }
+}
+
+struct MatchChecker<'a> {
+    enums: Vec<&'a EnumDef>,
+    variants: HashMap<&'a str, (usize, usize)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Visitor for MatchChecker<'_> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let ExprKind::Match(value, arms) = &expr.kind {
+            self.check_match(value, arms);
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+impl MatchChecker<'_> {
+    fn check_match(&mut self, value: &Expr, arms: &[Arm]) {
+        // Patterns naming unknown variants fail at runtime; there is nothing to check.
+        let Some(patterns) = arms.iter().map(|arm| self.lower(&arm.pattern)).collect::<Option<Vec<Pat>>>() else {
+            return;
+        };
+
+        let rows: Vec<Vec<Pat>> = patterns.into_iter().map(|pattern| vec![pattern]).collect();
+        for (i, arm) in arms.iter().enumerate() {
+            if !self.is_useful(&rows[..i], &rows[i]) {
+                self.diagnostics.push(Diagnostic::warning("unreachable match arm", arm.pattern.span));
+            }
+        }
+
+        if let Some(missing) = self.missing(&rows, 1) {
+            let missing = self.show(&missing[0]);
+            let diagnostic = Diagnostic::warning(format!("non-exhaustive match: `{}` not covered", missing), value.span)
+                .with_help("add an arm for it, or a `_` arm for every other value");
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn lower(&self, pattern: &Pattern) -> Option<Pat> {
+        let (name, fields) = match &pattern.kind {
+            PatternKind::Literal(literal) => return Some(Pat::Ctor(literal_ctor(literal), Vec::new())),
+            PatternKind::Wildcard => return Some(Pat::Any),
+            PatternKind::Binding(name) if !self.variants.contains_key(name.name.as_str()) => return Some(Pat::Any),
+            PatternKind::Binding(name) => (name, &[][..]),
+            PatternKind::Variant(name, fields) => (name, &fields[..]),
+        };
+
+        let (i, j) = *self.variants.get(name.name.as_str())?;
+        if self.enums[i].variants[j].fields.len() != fields.len() {
+            return None;
+        }
+        let fields = fields.iter().map(|field| self.lower(field)).collect::<Option<Vec<Pat>>>()?;
+        Some(Pat::Ctor(Ctor::Variant(i, j), fields))
+    }
+
+    fn arity(&self, ctor: &Ctor) -> usize {
+        match ctor {
+            Ctor::Variant(i, j) => self.enums[*i].variants[*j].fields.len(),
+            Ctor::Bool(_) | Ctor::Int(_) | Ctor::Float(_) | Ctor::String(_) => 0,
+        }
+    }
+
+    /// Every constructor of the type `ctor` belongs to, if they can be listed.
+    fn all_ctors(&self, ctor: &Ctor) -> Option<Vec<Ctor>> {
+        match ctor {
+            Ctor::Variant(i, _) => Some((0..self.enums[*i].variants.len()).map(|j| Ctor::Variant(*i, j)).collect()),
+            Ctor::Bool(_) => Some(vec![Ctor::Bool(true), Ctor::Bool(false)]),
+            Ctor::Int(_) | Ctor::Float(_) | Ctor::String(_) => None,
+        }
+    }
+
+    /// The constructors at the head of `rows`, and all of their type's if the
+    /// heads cover every one of them.
+    fn head_ctors(&self, rows: &[Vec<Pat>]) -> (Vec<Ctor>, Option<Vec<Ctor>>) {
+        let heads: Vec<Ctor> = rows.iter().filter_map(|row| match &row[0] {
+            Pat::Ctor(ctor, _) => Some(ctor.clone()),
+            Pat::Any => None,
+        }).collect();
+
+        let complete = heads.first()
+            .and_then(|ctor| self.all_ctors(ctor))
+            .filter(|all| all.iter().all(|ctor| heads.contains(ctor)));
+        (heads, complete)
+    }
+
+    /// The rows that can match a value built with `ctor`, with its fields in
+    /// place of the head.
+    fn specialize(&self, rows: &[Vec<Pat>], ctor: &Ctor) -> Vec<Vec<Pat>> {
+        rows.iter().filter_map(|row| {
+            let mut fields = match &row[0] {
+                Pat::Ctor(head, fields) if head == ctor => fields.clone(),
+                Pat::Ctor(..) => return None,
+                Pat::Any => vec![Pat::Any; self.arity(ctor)],
+            };
+            fields.extend_from_slice(&row[1..]);
+            Some(fields)
+        }).collect()
+    }
+
+    /// The rows that match anything at the head, without it.
+    fn default_rows(&self, rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+        rows.iter().filter(|row| row[0] == Pat::Any).map(|row| row[1..].to_vec()).collect()
+    }
+
+    /// Whether some values match `row` but none of `rows`.
+    fn is_useful(&self, rows: &[Vec<Pat>], row: &[Pat]) -> bool {
+        let Some((head, rest)) = row.split_first() else {
+            return rows.is_empty();
+        };
+
+        let expand = |ctor: &Ctor, fields: Vec<Pat>| {
+            let mut row = fields;
+            row.extend_from_slice(rest);
+            self.is_useful(&self.specialize(rows, ctor), &row)
+        };
+
+        match head {
+            Pat::Ctor(ctor, fields) => expand(ctor, fields.clone()),
+            Pat::Any => match self.head_ctors(rows) {
+                (_, Some(all)) => all.iter().any(|ctor| expand(ctor, vec![Pat::Any; self.arity(ctor)])),
+                (_, None) => self.is_useful(&self.default_rows(rows), rest),
+            },
+        }
+    }
+
+    /// Values, `width` at a time, that match none of `rows`.
+    fn missing(&self, rows: &[Vec<Pat>], width: usize) -> Option<Vec<Pat>> {
+        if width == 0 {
+            return rows.is_empty().then(Vec::new);
+        }
+
+        let (heads, complete) = self.head_ctors(rows);
+        if let Some(all) = complete {
+            return all.into_iter().find_map(|ctor| {
+                let arity = self.arity(&ctor);
+                let mut fields = self.missing(&self.specialize(rows, &ctor), arity + width - 1)?;
+                let rest = fields.split_off(arity);
+                Some(std::iter::once(Pat::Ctor(ctor, fields)).chain(rest).collect())
+            });
+        }
+
+        let mut missing = self.missing(&self.default_rows(rows), width - 1)?;
+        // Name a constructor the rows leave out, if the type has a list of them.
+        let uncovered = heads.first()
+            .and_then(|ctor| self.all_ctors(ctor))
+            .and_then(|all| all.into_iter().find(|ctor| !heads.contains(ctor)));
+        let head = match uncovered {
+            Some(ctor) => Pat::Ctor(ctor.clone(), vec![Pat::Any; self.arity(&ctor)]),
+            None => Pat::Any,
+        };
+        missing.insert(0, head);
+        Some(missing)
+    }
+
+    fn show(&self, pat: &Pat) -> String {
+        match pat {
+            Pat::Any => "_".to_string(),
+            Pat::Ctor(Ctor::Bool(value), _) => value.to_string(),
+            Pat::Ctor(Ctor::Int(value), _) => value.to_string(),
+            Pat::Ctor(Ctor::Float(value), _) => format!("{:?}", value),
+            Pat::Ctor(Ctor::String(value), _) => format!("{:?}", value),
+            Pat::Ctor(Ctor::Variant(i, j), fields) => {
+                let name = &self.enums[*i].variants[*j].name.name;
+                if fields.is_empty() {
+                    return name.clone();
+                }
+                let fields: Vec<String> = fields.iter().map(|field| self.show(field)).collect();
+                format!("{}({})", name, fields.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+use super::*;
+use super::super::{diagnostics, parser, scanner};
+
+    fn warnings(text: &str) -> Vec<String> {
+        let program = parser::Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+        check(&program).iter().map(|diagnostic| diagnostic.message.clone()).collect()
+    }
+
+    #[test]
+    fn check_accepts_exhaustive_matches() {
+        let text = r#"
+            enum Shape { Circle(r), Rect(w, h), Empty }
+            enum Option { Some(x), None }
+
+            fn main() {
+                match shape {
+                    Circle(r) => r,
+                    Rect(0, _) => 0,
+                    Rect(w, h) => w * h,
+                    Empty => 0,
+                };
+                match pair {
+                    Some(true) => 1,
+                    Some(false) => 2,
+                    None => 3,
+                };
+                match n { 1 => "one", -1 => "minus one", other => other };
+                match x { _ => 0 };
+            }
+        "#;
+
+        assert_eq!(warnings(text), Vec::<String>::new());
+    }
+
+    #[test]
+    fn check_warns_about_missing_values() {
+        let text = r#"
+            enum Shape { Circle(r), Rect(w, h), Empty }
+            enum Option { Some(x), None }
+
+            fn main() {
+                match shape { Circle(r) => r, Empty => 0 };
+                match option { Some(Circle(_)) => 1, None => 0 };
+                match flag { true => 1 };
+                match n { 0 => 1, 1 => 1 };
+                match x {};
+            }
+        "#;
+
+        assert_eq!(warnings(text), vec![
+            "non-exhaustive match: `Rect(_, _)` not covered",
+            "non-exhaustive match: `Some(Rect(_, _))` not covered",
+            "non-exhaustive match: `false` not covered",
+            "non-exhaustive match: `_` not covered",
+            "non-exhaustive match: `_` not covered",
+        ]);
+    }
+
+    #[test]
+    fn check_warns_about_unreachable_arms() {
+        let text = "enum Option { Some(x), None }\nfn main() {\n    print(match o {\n        Some(x) => x,\n        None => 0,\n        Some(1) => 1,\n        _ => 2,\n    });\n    match fn() { return match 1 { a => a, 1 => 1 }; } {};\n}";
+        let program = parser::Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+        let rendered: Vec<String> = check(&program).iter()
+            .map(|diagnostic| diagnostics::render(diagnostic, "test.rml", text, false))
+            .collect();
+
+        assert_eq!(rendered, vec![
+            concat!(
+                "warning: unreachable match arm\n",
+                " --> test.rml:6:9\n",
+                "  |\n",
+                "6 |         Some(1) => 1,\n",
+                "  |         ^^^^^^^\n",
+            ),
+            concat!(
+                "warning: unreachable match arm\n",
+                " --> test.rml:7:9\n",
+                "  |\n",
+                "7 |         _ => 2,\n",
+                "  |         ^\n",
+            ),
+            concat!(
+                "warning: non-exhaustive match: `_` not covered\n",
+                " --> test.rml:9:11\n",
+                "  |\n",
+                "9 |     match fn() { return match 1 { a => a, 1 => 1 }; } {};\n",
+                "  |           ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^\n",
+                "  = help: add an arm for it, or a `_` arm for every other value\n",
+            ),
+            concat!(
+                "warning: unreachable match arm\n",
+                " --> test.rml:9:43\n",
+                "  |\n",
+                "9 |     match fn() { return match 1 { a => a, 1 => 1 }; } {};\n",
+                "  |                                           ^\n",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn check_unifies_equal_numbers() {
+        let text = r#"
+            fn main() {
+                match n { 1 => "a", 1.0 => "b", 2.5 => "c", 0x10 => "d", 16 => "e", 16.0 => "f", "1" => "g", _ => "h" };
+            }
+        "#;
+
+        assert_eq!(warnings(text), vec![
+            "unreachable match arm",
+            "unreachable match arm",
+            "unreachable match arm",
+        ]);
+    }
+}
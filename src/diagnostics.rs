@@ -0,0 +1,253 @@
+use std::fmt::Write;
+
+use super::evaluator::RuntimeError;
+use super::parser::ParseError;
+use super::scanner::{LexError, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A span of source with a message shown next to its underline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Label { span, message: message.into() }
+    }
+}
+
+/// A problem in a source file, as reported to the user.
+///
+/// The primary label is underlined with `^`, secondary labels with `-`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub primary: Label,
+    pub secondary: Vec<Label>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic { severity: Severity::Error, message: message.into(), primary: Label::new(span, ""), secondary: Vec::new(), help: Vec::new() }
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic { severity: Severity::Warning, ..Diagnostic::error(message, span) }
+    }
+
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.secondary.push(label);
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(error: LexError) -> Self {
+        Diagnostic::error(error.message, error.span)
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        let diagnostic = Diagnostic { secondary: error.labels, ..Diagnostic::error(error.message, error.span) };
+        match error.help {
+            Some(help) => diagnostic.with_help(help),
+            None => diagnostic,
+        }
+    }
+}
+
+impl From<RuntimeError> for Diagnostic {
+    fn from(error: RuntimeError) -> Self {
+        Diagnostic { secondary: error.labels, ..Diagnostic::error(error.message, error.span) }
+    }
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Renders a diagnostic like a compiler does: the message, `file:line:col`,
+/// the source lines with the labels underlined, then the help notes.
+/// `color` adds ANSI colours for terminals.
+pub fn render(diagnostic: &Diagnostic, file_name: &str, source: &str, color: bool) -> String {
+    let style = Style { color };
+    let (name, color_code) = match diagnostic.severity {
+        Severity::Error => ("error", "1;31"),
+        Severity::Warning => ("warning", "1;33"),
+    };
+    let gutter_code = "1;34";
+
+    let mut labels: Vec<(&Label, bool)> = vec![(&diagnostic.primary, true)];
+    labels.extend(diagnostic.secondary.iter().map(|label| (label, false)));
+    labels.retain(|(label, _)| label.span.line > 0);
+    labels.sort_by_key(|(label, _)| (label.span.line, label.span.column));
+
+    let width = labels.iter().map(|(label, _)| label.span.line.to_string().len()).max().unwrap_or(1);
+    let gutter = style.paint(gutter_code, &format!("{} |", " ".repeat(width)));
+
+    let mut out = String::new();
+    writeln!(out, "{}{}", style.paint(color_code, name), style.paint("1", &format!(": {}", diagnostic.message))).unwrap();
+
+    let primary = diagnostic.primary.span;
+    if primary.line == 0 {
+        writeln!(out, "{}{} {}", " ".repeat(width), style.paint(gutter_code, "-->"), file_name).unwrap();
+    } else {
+        writeln!(out, "{}{} {}:{}:{}", " ".repeat(width), style.paint(gutter_code, "-->"), file_name, primary.line, primary.column).unwrap();
+    }
+
+    if !labels.is_empty() {
+        writeln!(out, "{}", gutter).unwrap();
+    }
+
+    let mut last_line = 0;
+    for (label, is_primary) in labels {
+        let line_start = source[..label.span.start.min(source.len())].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[line_start..].find('\n').map_or(source.len(), |i| line_start + i);
+        let line = &source[line_start..line_end];
+
+        if label.span.line != last_line {
+            if last_line != 0 && label.span.line > last_line + 1 {
+                writeln!(out, "{}", style.paint(gutter_code, "...")).unwrap();
+            }
+            let number = style.paint(gutter_code, &format!("{:>width$} |", label.span.line, width = width));
+            writeln!(out, "{} {}", number, expand_tabs(line)).unwrap();
+            last_line = label.span.line;
+        }
+
+        let start = label.span.start.clamp(line_start, line_end);
+        let end = label.span.end.clamp(start, line_end);
+        let indent = expand_tabs(&source[line_start..start]).chars().count();
+        let length = expand_tabs(&source[start..end]).chars().count().max(1);
+        let (mark, code) = if is_primary { ("^", color_code) } else { ("-", gutter_code) };
+        let underline = format!("{} {}", mark.repeat(length), label.message);
+
+        writeln!(out, "{} {}{}", gutter, " ".repeat(indent), style.paint(code, underline.trim_end())).unwrap();
+    }
+
+    for help in &diagnostic.help {
+        writeln!(out, "{} {} {}", " ".repeat(width), style.paint("1", "= help:"), help).unwrap();
+    }
+
+    out
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', "    ")
+}
+
+#[cfg(test)]
+mod tests {
+use super::*;
+use super::super::{evaluator, parser, scanner};
+
+    fn render_all(source: &str) -> String {
+        let program = parser::Parser::new(scanner::Scanner::new(source)).parse();
+        let diagnostics: Vec<Diagnostic> = match program {
+            Err(errors) => errors.into_iter().map(Diagnostic::from).collect(),
+            Ok(ast) => match evaluator::Evaluator::new(Vec::new()).apply(ast) {
+                Err(error) => vec![Diagnostic::from(error)],
+                Ok(_) => vec![],
+            },
+        };
+
+        diagnostics.iter().map(|diagnostic| render(diagnostic, "test.rml", source, false)).collect::<Vec<String>>().join("\n")
+    }
+
+    #[test]
+    fn render_syntax_errors() {
+        let source = "fn main() {\n\tx = (1 + 2;\n    print(\"a\")\n    y = 1;\n}\nz = 0;\n";
+
+        assert_eq!(render_all(source), concat!(
+            "error: expected `)`, found `;`\n",
+            " --> test.rml:2:12\n",
+            "  |\n",
+            "2 |     x = (1 + 2;\n",
+            "  |         - unclosed `(`\n",
+            "  |               ^\n",
+            "\n",
+            "error: expected `;`, found `y`\n",
+            " --> test.rml:4:5\n",
+            "  |\n",
+            "3 |     print(\"a\")\n",
+            "  |              - expected `;` after this\n",
+            "4 |     y = 1;\n",
+            "  |     ^\n",
+            "\n",
+            "error: expected an item, found `z`\n",
+            " --> test.rml:6:1\n",
+            "  |\n",
+            "6 | z = 0;\n",
+            "  | ^\n",
+            "  = help: items are `fn`, `struct`, `impl`, `enum`, `const` and `let` definitions\n",
+        ));
+    }
+
+    #[test]
+    fn render_lexical_and_runtime_errors() {
+        let source = "fn main() {\n    print(\"oops);\n}";
+        assert_eq!(render_all(source), concat!(
+            "error: unterminated string literal\n",
+            " --> test.rml:2:11\n",
+            "  |\n",
+            "2 |     print(\"oops);\n",
+            "  |           ^\n",
+            "\n",
+            "error: expected `}`, found end of input\n",
+            " --> test.rml:3:2\n",
+            "  |\n",
+            "1 | fn main() {\n",
+            "  |           - unclosed `{`\n",
+            "...\n",
+            "3 | }\n",
+            "  |  ^\n",
+        ));
+
+        let source = "fn main() {\n    add(1);\n}\n\nfn add(a, b) {\n    return a + b;\n}\n";
+        assert_eq!(render_all(source), concat!(
+            "error: `add` takes 2 argument(s) but 1 were given\n",
+            " --> test.rml:2:5\n",
+            "  |\n",
+            "2 |     add(1);\n",
+            "  |     ^^^^^^\n",
+            "...\n",
+            "5 | fn add(a, b) {\n",
+            "  |    --- `add` defined here\n",
+        ));
+
+        assert_eq!(render_all("fn helper() {}"), "error: no `main` function\n --> test.rml\n");
+    }
+
+    #[test]
+    fn render_with_color() {
+        let diagnostic = Diagnostic::warning("unused", Span { start: 0, end: 1, line: 1, column: 1 });
+        let rendered = render(&diagnostic, "test.rml", "x", true);
+
+        assert!(rendered.starts_with("\x1b[1;33mwarning\x1b[0m\x1b[1m: unused\x1b[0m\n"));
+        assert!(rendered.contains("\x1b[1;33m^\x1b[0m"));
+    }
+}
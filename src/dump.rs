@@ -0,0 +1,386 @@
+use std::fmt::Write;
+
+use super::parser::{
+    Block, EnumDef, Expr, ExprKind, FuncDef, Global, Ident, Impl, Item, Literal, Pattern, PatternKind, Program, Stmt, StmtKind,
+    StructDef,
+};
+use super::scanner::{Span, Token, Trivia};
+
+/// Plain data that prints as JSON or as an S-expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    /// A name such as a token kind or an operator: a JSON string, a bare S-expression atom.
+    Symbol(String),
+    List(Vec<Node>),
+    /// Named fields, tagged with the kind of thing they describe. In JSON the
+    /// tag is the `"kind"` field; in an S-expression it leads the list.
+    Record(Option<String>, Vec<(&'static str, Node)>),
+}
+
+impl Node {
+    fn record(kind: &str, mut fields: Vec<(&'static str, Node)>, span: Span) -> Node {
+        fields.push(("span", span_node(span)));
+        Node::Record(Some(kind.to_string()), fields)
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    pub fn to_sexp(&self) -> String {
+        let mut out = String::new();
+        self.write_sexp(&mut out);
+        out
+    }
+
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Node::Null => out.push_str("null"),
+            Node::Bool(value) => write!(out, "{}", value).unwrap(),
+            Node::Int(value) => write!(out, "{}", value).unwrap(),
+            Node::Float(value) if value.is_finite() => write!(out, "{:?}", value).unwrap(),
+            Node::Float(_) => out.push_str("null"),
+            Node::String(value) | Node::Symbol(value) => write_string(out, value),
+            Node::List(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    item.write_json(out);
+                }
+                out.push(']');
+            }
+            Node::Record(kind, fields) => {
+                out.push('{');
+                if let Some(kind) = kind {
+                    out.push_str("\"kind\":");
+                    write_string(out, kind);
+                }
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 || kind.is_some() {
+                        out.push(',');
+                    }
+                    write_string(out, name);
+                    out.push(':');
+                    value.write_json(out);
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    fn write_sexp(&self, out: &mut String) {
+        match self {
+            Node::Null => out.push_str("nil"),
+            Node::Bool(value) => write!(out, "{}", value).unwrap(),
+            Node::Int(value) => write!(out, "{}", value).unwrap(),
+            Node::Float(value) => write!(out, "{:?}", value).unwrap(),
+            Node::String(value) => write_string(out, value),
+            Node::Symbol(value) => out.push_str(value),
+            Node::List(items) => {
+                out.push('(');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(' ');
+                    }
+                    item.write_sexp(out);
+                }
+                out.push(')');
+            }
+            Node::Record(kind, fields) => {
+                out.push('(');
+                if let Some(kind) = kind {
+                    out.push_str(kind);
+                }
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 || kind.is_some() {
+                        out.push(' ');
+                    }
+                    write!(out, ":{} ", name).unwrap();
+                    value.write_sexp(out);
+                }
+                out.push(')');
+            }
+        }
+    }
+}
+
+/// Writes `value` as a double-quoted string with JSON escapes.
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn span_node(span: Span) -> Node {
+    Node::Record(None, vec![
+        ("start", Node::Int(span.start as i64)),
+        ("end", Node::Int(span.end as i64)),
+        ("line", Node::Int(span.line as i64)),
+        ("column", Node::Int(span.column as i64)),
+    ])
+}
+
+fn symbol(value: impl std::fmt::Debug) -> Node {
+    Node::Symbol(format!("{:?}", value))
+}
+
+fn optional<T>(value: Option<T>, node: impl FnOnce(T) -> Node) -> Node {
+    value.map_or(Node::Null, node)
+}
+
+pub fn tokens(tokens: &[Token]) -> Node {
+    Node::List(tokens.iter().map(token).collect())
+}
+
+pub fn token(token: &Token) -> Node {
+    Node::record(&format!("{:?}", token.kind), vec![
+        ("value", Node::String(token.value.clone())),
+        ("trivia", Node::List(token.trivia.iter().map(trivia).collect())),
+    ], token.span)
+}
+
+fn trivia(trivia: &Trivia) -> Node {
+    Node::record(&format!("{:?}", trivia.kind), vec![("text", Node::String(trivia.text.clone()))], trivia.span)
+}
+
+pub fn program(program: &Program) -> Node {
+    Node::List(program.iter().map(item).collect())
+}
+
+fn item(item: &Item) -> Node {
+    match item {
+        Item::FuncDef(func_def) => self::func_def(func_def),
+        Item::Global(global) => self::global(global),
+        Item::Struct(struct_def) => self::struct_def(struct_def),
+        Item::Impl(impl_def) => self::impl_def(impl_def),
+        Item::Enum(enum_def) => self::enum_def(enum_def),
+    }
+}
+
+fn func_def(func_def: &FuncDef) -> Node {
+    Node::record("FuncDef", vec![
+        ("name", ident(&func_def.name)),
+        ("args", Node::List(func_def.args.iter().map(ident).collect())),
+        ("body", block(&func_def.body)),
+        ("doc", optional(func_def.doc.clone(), Node::String)),
+    ], func_def.span)
+}
+
+fn global(global: &Global) -> Node {
+    Node::record("Global", vec![
+        ("name", ident(&global.name)),
+        ("value", expr(&global.value)),
+        ("mutable", Node::Bool(global.mutable)),
+        ("doc", optional(global.doc.clone(), Node::String)),
+    ], global.span)
+}
+
+fn struct_def(struct_def: &StructDef) -> Node {
+    Node::record("Struct", vec![
+        ("name", ident(&struct_def.name)),
+        ("fields", Node::List(struct_def.fields.iter().map(ident).collect())),
+        ("doc", optional(struct_def.doc.clone(), Node::String)),
+    ], struct_def.span)
+}
+
+fn impl_def(impl_def: &Impl) -> Node {
+    Node::record("Impl", vec![
+        ("name", ident(&impl_def.name)),
+        ("methods", Node::List(impl_def.methods.iter().map(func_def).collect())),
+    ], impl_def.span)
+}
+
+fn enum_def(enum_def: &EnumDef) -> Node {
+    let variants = enum_def.variants.iter().map(|variant| {
+        Node::record("Variant", vec![
+            ("name", ident(&variant.name)),
+            ("fields", Node::List(variant.fields.iter().map(ident).collect())),
+        ], variant.span)
+    });
+
+    Node::record("Enum", vec![
+        ("name", ident(&enum_def.name)),
+        ("variants", Node::List(variants.collect())),
+        ("doc", optional(enum_def.doc.clone(), Node::String)),
+    ], enum_def.span)
+}
+
+fn ident(ident: &Ident) -> Node {
+    Node::record("Ident", vec![("name", Node::String(ident.name.clone()))], ident.span)
+}
+
+fn block(block: &Block) -> Node {
+    Node::List(block.iter().map(stmt).collect())
+}
+
+fn stmt(stmt: &Stmt) -> Node {
+    let (kind, fields) = match &stmt.kind {
+        StmtKind::Expr(value) => ("Expr", vec![("expr", expr(value))]),
+        StmtKind::Assign(name, value) => ("Assign", vec![("name", ident(name)), ("value", expr(value))]),
+        StmtKind::SetIndex(target, index, value) => ("SetIndex", vec![
+            ("target", expr(target)),
+            ("index", expr(index)),
+            ("value", expr(value)),
+        ]),
+        StmtKind::SetField(target, field, value) => ("SetField", vec![
+            ("target", expr(target)),
+            ("field", ident(field)),
+            ("value", expr(value)),
+        ]),
+        StmtKind::While(cond, body) => ("While", vec![("cond", expr(cond)), ("body", block(body))]),
+        StmtKind::For(name, iterable, body) => ("For", vec![
+            ("name", ident(name)),
+            ("iterable", expr(iterable)),
+            ("body", block(body)),
+        ]),
+        StmtKind::If(cond, then, otherwise) => ("If", vec![
+            ("cond", expr(cond)),
+            ("then", block(then)),
+            ("else", optional(otherwise.as_ref(), block)),
+        ]),
+        StmtKind::Break => ("Break", vec![]),
+        StmtKind::Continue => ("Continue", vec![]),
+        StmtKind::Return(value) => ("Return", vec![("value", optional(value.as_ref(), expr))]),
+    };
+
+    Node::record(kind, fields, stmt.span)
+}
+
+fn expr(expr: &Expr) -> Node {
+    let (kind, fields) = match &expr.kind {
+        ExprKind::Literal(value) => ("Literal", vec![("value", literal(value))]),
+        ExprKind::Ident(name) => ("Ident", vec![("name", Node::String(name.name.clone()))]),
+        ExprKind::Call(callee, args) => ("Call", vec![
+            ("callee", self::expr(callee)),
+            ("args", Node::List(args.iter().map(self::expr).collect())),
+        ]),
+        ExprKind::Index(target, index) => ("Index", vec![("target", self::expr(target)), ("index", self::expr(index))]),
+        ExprKind::List(items) => ("List", vec![("items", Node::List(items.iter().map(self::expr).collect()))]),
+        ExprKind::Map(entries) => {
+            let entries = entries.iter().map(|(key, value)| {
+                Node::Record(None, vec![("key", self::expr(key)), ("value", self::expr(value))])
+            });
+            ("Map", vec![("entries", Node::List(entries.collect()))])
+        }
+        ExprKind::Field(target, field) => ("Field", vec![("target", self::expr(target)), ("field", ident(field))]),
+        ExprKind::Struct(name, fields) => {
+            let fields = fields.iter().map(|(field, value)| {
+                Node::Record(None, vec![("field", ident(field)), ("value", self::expr(value))])
+            });
+            ("Struct", vec![("name", ident(name)), ("fields", Node::List(fields.collect()))])
+        }
+        ExprKind::Match(value, arms) => {
+            let arms = arms.iter().map(|arm| {
+                Node::record("Arm", vec![("pattern", pattern(&arm.pattern)), ("body", self::expr(&arm.body))], arm.span)
+            });
+            ("Match", vec![("value", self::expr(value)), ("arms", Node::List(arms.collect()))])
+        }
+        ExprKind::Lambda(args, body) => ("Lambda", vec![
+            ("args", Node::List(args.iter().map(ident).collect())),
+            ("body", block(body)),
+        ]),
+        ExprKind::Unary(op, operand) => ("Unary", vec![("op", symbol(op)), ("operand", self::expr(operand))]),
+        ExprKind::Binary(op, lhs, rhs) => ("Binary", vec![
+            ("op", symbol(op)),
+            ("lhs", self::expr(lhs)),
+            ("rhs", self::expr(rhs)),
+        ]),
+        ExprKind::Range(start, end) => ("Range", vec![("start", self::expr(start)), ("end", self::expr(end))]),
+    };
+
+    Node::record(kind, fields, expr.span)
+}
+
+fn pattern(pattern: &Pattern) -> Node {
+    let (kind, fields) = match &pattern.kind {
+        PatternKind::Literal(value) => ("Literal", vec![("value", literal(value))]),
+        PatternKind::Wildcard => ("Wildcard", vec![]),
+        PatternKind::Binding(name) => ("Binding", vec![("name", ident(name))]),
+        PatternKind::Variant(name, fields) => ("Variant", vec![
+            ("name", ident(name)),
+            ("fields", Node::List(fields.iter().map(self::pattern).collect())),
+        ]),
+    };
+
+    Node::record(kind, fields, pattern.span)
+}
+
+fn literal(literal: &Literal) -> Node {
+    match literal {
+        Literal::Int(value) => Node::Int(*value),
+        Literal::Float(value) => Node::Float(*value),
+        Literal::String(value) => Node::String(value.clone()),
+        Literal::Bool(value) => Node::Bool(*value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+use super::*;
+use super::super::{parser, scanner};
+
+    #[test]
+    fn dump_tokens() {
+        let lexed: Vec<Token> = scanner::Lexer::new("/// hi\nx = \"a\\\"b\" @").collect();
+
+        assert_eq!(tokens(&lexed[..1]).to_json(), concat!(
+            r#"[{"kind":"Ident","value":"x","trivia":["#,
+            r#"{"kind":"DocComment","text":"/// hi","span":{"start":0,"end":6,"line":1,"column":1}}"#,
+            r#"],"span":{"start":7,"end":8,"line":2,"column":1}}]"#,
+        ));
+        assert_eq!(token(&lexed[2]).to_json(), r#"{"kind":"String","value":"\"a\"b\"","trivia":[],"span":{"start":11,"end":17,"line":2,"column":5}}"#);
+        assert_eq!(token(&lexed[3]).to_sexp(), r#"(Error :value "@" :trivia () :span (:start 18 :end 19 :line 2 :column 12))"#);
+    }
+
+    #[test]
+    fn dump_program() {
+        let text = "/// Adds.\nfn add(a) {\n    return a + 1.5;\n}";
+        let ast = parser::Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+
+        assert_eq!(program(&ast).to_sexp(), concat!(
+            "((FuncDef",
+            " :name (Ident :name \"add\" :span (:start 13 :end 16 :line 2 :column 4))",
+            " :args ((Ident :name \"a\" :span (:start 17 :end 18 :line 2 :column 8)))",
+            " :body ((Return",
+            " :value (Binary :op Add",
+            " :lhs (Ident :name \"a\" :span (:start 33 :end 34 :line 3 :column 12))",
+            " :rhs (Literal :value 1.5 :span (:start 37 :end 40 :line 3 :column 16))",
+            " :span (:start 33 :end 40 :line 3 :column 12))",
+            " :span (:start 26 :end 41 :line 3 :column 5)))",
+            " :doc \"Adds.\"",
+            " :span (:start 10 :end 43 :line 2 :column 1)))",
+        ));
+
+        let json = program(&ast).to_json();
+        assert!(json.starts_with(r#"[{"kind":"FuncDef","name":{"kind":"Ident","name":"add","span":{"#), "{}", json);
+        assert!(json.contains(r#"{"kind":"Binary","op":"Add","lhs":{"kind":"Ident","name":"a","#), "{}", json);
+        assert!(json.ends_with(r#""doc":"Adds.","span":{"start":10,"end":43,"line":2,"column":1}}]"#), "{}", json);
+    }
+
+    #[test]
+    fn dump_escapes_strings() {
+        let node = Node::List(vec![Node::String("tab\tnull\0é".to_string()), Node::Null, Node::Float(f64::NAN)]);
+
+        assert_eq!(node.to_json(), r#"["tab\tnull\u0000é",null,null]"#);
+        assert_eq!(node.to_sexp(), r#"("tab\tnull\u0000é" nil NaN)"#);
+    }
+}
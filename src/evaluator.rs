@@ -1,62 +1,1789 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
 use std::{rc::Rc, cell::RefCell};
 
+use super::diagnostics::Label;
 use super::parser;
+use super::parser::{BinOp, ExprKind, StmtKind, UnaryOp};
+use super::scanner::Span;
 
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    Func(Func),
+    /// Lists and maps are shared: a copy of the value sees the same items.
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<BTreeMap<String, Value>>>),
+    /// Structs are shared like lists.
+    Struct(Rc<Instance>),
+    Enum(Rc<EnumValue>),
+    /// `start..end`
+    Range(i64, i64),
+    Unit,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f)
+    }
+}
+
+/// Lists and maps are equal if their items are, structs and enum values if
+/// their types, variants and fields are. A value is always equal to itself.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.equals(other)
+    }
+}
+
+/// Values nest as deeply as a program builds them, as with `xs = [xs];` in a
+/// loop, so the last reference to one takes its contents apart a level at a
+/// time instead of dropping them recursively.
+impl Drop for Value {
+    fn drop(&mut self) {
+        let mut values = Vec::new();
+        self.take_contents(&mut values);
+        while let Some(mut value) = values.pop() {
+            value.take_contents(&mut values);
+        }
+    }
+}
+
+/// The address of a shared value, to recognise it when it contains itself.
+fn address<T>(value: &Rc<T>) -> *const () {
+    Rc::as_ptr(value) as *const ()
+}
+
+/// What is left to write of a value.
+enum Step {
+    /// A value, quoted if it is a string inside another value.
+    Value(Value, bool),
+    Text(Cow<'static, str>),
+    /// The end of the list, map or struct at this address.
+    Leave(*const ()),
+}
+
+/// Queues `items` to be written, separated by commas and followed by `close`.
+fn queue_items(steps: &mut Vec<Step>, items: Vec<Vec<Step>>, close: &'static str) {
+    steps.push(Step::Text(close.into()));
+    for (i, item) in items.into_iter().enumerate().rev() {
+        steps.extend(item.into_iter().rev());
+        if i > 0 {
+            steps.push(Step::Text(", ".into()));
+        }
+    }
+}
+
+impl Value {
+    fn type_name(&self) -> &str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Func(_) => "function",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Struct(instance) => &instance.ty.name.name,
+            Value::Enum(value) => &value.ty.name.name,
+            Value::Range(..) => "range",
+            Value::Unit => "unit",
+        }
+    }
+
+    /// Writes the value, keeping what is left to write on a stack rather
+    /// than recursing into nested values.
+    fn write(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut steps = vec![Step::Value(self.clone(), false)];
+        // The lists, maps and structs being written, to recognise one that
+        // contains itself, as after `xs = []; push(xs, xs);`.
+        let mut open = HashSet::new();
+        while let Some(step) = steps.pop() {
+            let (value, quoted) = match step {
+                Step::Value(value, quoted) => (value, quoted),
+                Step::Text(text) => {
+                    f.write_str(&text)?;
+                    continue;
+                }
+                Step::Leave(address) => {
+                    open.remove(&address);
+                    continue;
+                }
+            };
+
+            match &value {
+                Value::Int(value) => write!(f, "{}", value)?,
+                Value::Float(value) => write!(f, "{:?}", value)?,
+                Value::String(value) if quoted => write!(f, "{:?}", value)?,
+                Value::String(value) => write!(f, "{}", value)?,
+                Value::Bool(value) => write!(f, "{}", value)?,
+                Value::Func(func) => write!(f, "{}", func)?,
+                Value::List(items) if open.contains(&address(items)) => write!(f, "[...]")?,
+                Value::List(items) => {
+                    write!(f, "[")?;
+                    open.insert(address(items));
+                    steps.push(Step::Leave(address(items)));
+                    let items = items.borrow().iter().map(|item| vec![Step::Value(item.clone(), true)]).collect();
+                    queue_items(&mut steps, items, "]");
+                }
+                Value::Map(entries) if open.contains(&address(entries)) => write!(f, "{{...}}")?,
+                Value::Map(entries) => {
+                    write!(f, "{{")?;
+                    open.insert(address(entries));
+                    steps.push(Step::Leave(address(entries)));
+                    let entries = entries.borrow().iter()
+                        .map(|(key, value)| vec![Step::Text(format!("{:?}: ", key).into()), Step::Value(value.clone(), true)])
+                        .collect();
+                    queue_items(&mut steps, entries, "}");
+                }
+                Value::Struct(instance) if open.contains(&address(instance)) => write!(f, "{} {{...}}", instance.ty.name.name)?,
+                Value::Struct(instance) => {
+                    let fields = instance.fields.borrow();
+                    if fields.is_empty() {
+                        write!(f, "{} {{}}", instance.ty.name.name)?;
+                        continue;
+                    }
+
+                    write!(f, "{} {{ ", instance.ty.name.name)?;
+                    open.insert(address(instance));
+                    steps.push(Step::Leave(address(instance)));
+                    let fields = instance.ty.fields.iter().zip(fields.iter())
+                        .map(|(name, value)| vec![Step::Text(format!("{}: ", name).into()), Step::Value(value.clone(), true)])
+                        .collect();
+                    queue_items(&mut steps, fields, " }");
+                }
+                Value::Enum(value) => {
+                    write!(f, "{}", value.ty.variants[value.variant].name.name)?;
+                    if value.fields.is_empty() {
+                        continue;
+                    }
+
+                    write!(f, "(")?;
+                    let fields = value.fields.iter().map(|field| vec![Step::Value(field.clone(), true)]).collect();
+                    queue_items(&mut steps, fields, ")");
+                }
+                Value::Range(start, end) => write!(f, "{}..{}", start, end)?,
+                Value::Unit => write!(f, "()")?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compares the values, keeping the pairs of items left to compare on a
+    /// stack rather than recursing into nested values.
+    fn equals(&self, other: &Value) -> bool {
+        // `Err` ends the pair of lists, maps or structs at those addresses.
+        let mut pairs = vec![Ok((self.clone(), other.clone()))];
+        // The pairs being compared. One met again inside itself is equal as far
+        // as the comparison has got.
+        let mut open = HashSet::new();
+        while let Some(pair) = pairs.pop() {
+            let (value, other) = match pair {
+                Ok(pair) => pair,
+                Err(addresses) => {
+                    open.remove(&addresses);
+                    continue;
+                }
+            };
+
+            let shared = match (&value, &other) {
+                (Value::List(items), Value::List(other)) => Some((address(items), address(other))),
+                (Value::Map(entries), Value::Map(other)) => Some((address(entries), address(other))),
+                (Value::Struct(instance), Value::Struct(other)) => Some((address(instance), address(other))),
+                _ => None,
+            };
+            if let Some(addresses) = shared {
+                if addresses.0 == addresses.1 || open.contains(&addresses) {
+                    continue;
+                }
+            }
+
+            let items: Vec<(Value, Value)> = match (&value, &other) {
+                (Value::Int(value), Value::Int(other)) if value == other => continue,
+                (Value::Float(value), Value::Float(other)) if value == other => continue,
+                (Value::String(value), Value::String(other)) if value == other => continue,
+                (Value::Bool(value), Value::Bool(other)) if value == other => continue,
+                (Value::Func(func), Value::Func(other)) if func == other => continue,
+                (Value::Range(start, end), Value::Range(other_start, other_end)) if start == other_start && end == other_end => continue,
+                (Value::Unit, Value::Unit) => continue,
+                (Value::List(items), Value::List(other)) => {
+                    let (items, other) = (items.borrow(), other.borrow());
+                    if items.len() != other.len() {
+                        return false;
+                    }
+                    items.iter().cloned().zip(other.iter().cloned()).collect()
+                }
+                (Value::Map(entries), Value::Map(other)) => {
+                    let (entries, other) = (entries.borrow(), other.borrow());
+                    if !entries.keys().eq(other.keys()) {
+                        return false;
+                    }
+                    entries.values().cloned().zip(other.values().cloned()).collect()
+                }
+                (Value::Struct(instance), Value::Struct(other)) if Rc::ptr_eq(&instance.ty, &other.ty) => {
+                    instance.fields.borrow().iter().cloned().zip(other.fields.borrow().iter().cloned()).collect()
+                }
+                (Value::Enum(value), Value::Enum(other)) if Rc::ptr_eq(&value.ty, &other.ty) && value.variant == other.variant => {
+                    value.fields.iter().cloned().zip(other.fields.iter().cloned()).collect()
+                }
+                _ => return false,
+            };
+
+            if let Some(addresses) = shared {
+                open.insert(addresses);
+                pairs.push(Err(addresses));
+            }
+            pairs.extend(items.into_iter().rev().map(Ok));
+        }
+
+        true
+    }
+
+    /// Moves the values only this one holds into `values`, leaving it empty.
+    fn take_contents(&mut self, values: &mut Vec<Value>) {
+        match self {
+            Value::List(items) => {
+                if let Some(items) = Rc::get_mut(items) {
+                    values.append(items.get_mut());
+                }
+            }
+            Value::Map(entries) => {
+                if let Some(entries) = Rc::get_mut(entries) {
+                    values.extend(std::mem::take(entries.get_mut()).into_values());
+                }
+            }
+            Value::Struct(instance) => {
+                if let Some(instance) = Rc::get_mut(instance) {
+                    values.append(instance.fields.get_mut());
+                }
+            }
+            Value::Enum(value) => {
+                if let Some(value) = Rc::get_mut(value) {
+                    values.append(&mut value.fields);
+                }
+            }
+            // A closure holds the values of the call it was made in.
+            Value::Func(Func::User(closure)) => {
+                if let Some(env) = Rc::get_mut(closure).and_then(|closure| Rc::get_mut(&mut closure.env)) {
+                    values.extend(env.get_mut().vars.drain().map(|(_, value)| value));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A type declared with `struct`, with the methods of its `impl` blocks.
+#[derive(Debug)]
+pub struct StructType {
+    name: parser::Ident,
+    fields: Vec<String>,
+    methods: HashMap<String, Func>,
+}
+
+impl StructType {
+    /// Error for a field the struct does not declare.
+    fn no_field(&self, field: &parser::Ident) -> RuntimeError {
+        RuntimeError {
+            message: format!("`{}` has no field `{}`", self.name.name, field.name),
+            span: field.span,
+            labels: vec![Label::new(self.name.span, format!("`{}` defined here", self.name.name))],
+        }
+    }
+}
+
+/// A value of a struct type, with its fields in declaration order.
+#[derive(Debug)]
+pub struct Instance {
+    ty: Rc<StructType>,
+    fields: RefCell<Vec<Value>>,
+}
+
+/// A type declared with `enum`.
+#[derive(Debug)]
+pub struct EnumType {
+    name: parser::Ident,
+    variants: Vec<parser::Variant>,
+}
+
+/// A value of an enum type: one of its variants and the values of its fields.
+#[derive(Debug)]
+pub struct EnumValue {
+    ty: Rc<EnumType>,
+    variant: usize,
+    fields: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Span,
+    /// Other places in the source that explain the error.
+    pub labels: Vec<Label>,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Runtime Error at line {}, column {}: {}", self.span.line, self.span.column, self.message)
+    }
+}
+
+fn error<T>(message: String, span: Span) -> Result<T, RuntimeError> {
+    Err(RuntimeError { message, span, labels: Vec::new() })
+}
+
+type Builtin = fn(&mut dyn Write, &[Value]) -> Result<Value, String>;
+
+/// A function value: a builtin, a user function with the env it was created
+/// in, or the constructor of an enum variant with fields.
+#[derive(Clone)]
+pub enum Func {
+    Builtin(String, Builtin),
+    User(Rc<Closure>),
+    Variant(Rc<EnumType>, usize),
+}
+
+pub struct Closure {
+    /// `None` for `fn(...) { ... }` expressions.
+    name: Option<parser::Ident>,
+    args: Vec<parser::Ident>,
+    body: parser::Block,
+    /// Where the function was written.
+    span: Span,
+    env: Rc<RefCell<Env>>,
+    /// Methods take `self` as their first argument, which callers do not pass themselves.
+    method: bool,
+}
+
+impl fmt::Display for Func {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Func::Builtin(name, _) => write!(f, "<fn {}>", name),
+            Func::User(closure) => match &closure.name {
+                Some(name) => write!(f, "<fn {}>", name.name),
+                None => write!(f, "<fn>"),
+            },
+            Func::Variant(ty, variant) => write!(f, "<fn {}>", ty.variants[*variant].name.name),
+        }
+    }
+}
+
+impl fmt::Debug for Func {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// Functions are equal only to themselves.
+impl PartialEq for Func {
+    fn eq(&self, other: &Func) -> bool {
+        match (self, other) {
+            (Func::Builtin(name, _), Func::Builtin(other, _)) => name == other,
+            (Func::User(closure), Func::User(other)) => Rc::ptr_eq(closure, other),
+            (Func::Variant(ty, variant), Func::Variant(other, other_variant)) => {
+                Rc::ptr_eq(ty, other) && variant == other_variant
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Variables of one function call, or the globals. Lookups fall back to the parent.
+#[derive(Default)]
 struct Env {
-    func_table: HashMap<String, fn(Vec<String>)->()>,
+    vars: HashMap<String, Value>,
+    /// Names in `vars` that may not be assigned again.
+    constants: HashSet<String>,
+    parent: Option<Rc<RefCell<Env>>>,
+    /// Set for one pass of a `for` loop, which holds just the loop variable.
+    /// Other names assigned in the body are defined in the parent.
+    pass: bool,
 }
 
-struct Evaluator {
-    env: Rc<RefCell<Env>>
+impl Env {
+    fn child(parent: &Rc<RefCell<Env>>) -> Rc<RefCell<Env>> {
+        Rc::new(RefCell::new(Env { parent: Some(parent.clone()), ..Env::default() }))
+    }
+
+    fn pass(parent: &Rc<RefCell<Env>>, name: String, value: Value) -> Rc<RefCell<Env>> {
+        let vars = HashMap::from([(name, value)]);
+        Rc::new(RefCell::new(Env { vars, parent: Some(parent.clone()), pass: true, ..Env::default() }))
+    }
+
+    fn get(&self, name: &str) -> Option<Value> {
+        match self.vars.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.parent.as_ref()?.borrow().get(name),
+        }
+    }
+
+    /// Assigns to the innermost existing variable, or defines it here, or in
+    /// the parent of a loop pass.
+    fn assign(&mut self, name: &str, value: Value) -> Result<(), String> {
+        if self.constants.contains(name) {
+            return Err(format!("cannot assign to constant `{}`", name));
+        }
+
+        if !self.vars.contains_key(name) {
+            if let Some(parent) = &self.parent {
+                if self.pass || parent.borrow().get(name).is_some() {
+                    return parent.borrow_mut().assign(name, value);
+                }
+            }
+        }
+
+        self.vars.insert(name.to_string(), value);
+        Ok(())
+    }
 }
 
-impl Evaluator {
-    pub fn new(&mut self) -> Self {
-        let funcs: HashMap<String, fn(Vec<String>)->()> = HashMap::new();
+/// How a statement finished.
+enum Flow {
+    Normal,
+    Break(Span),
+    Continue(Span),
+    Return(Value),
+}
 
-        let env = Rc::new(RefCell::new(Env { func_table: funcs}));
-        self.set("print".to_string(), |args| { syscall_stdout(&args[0])});
+/// Where a `for` loop is in the value it goes over.
+enum Iter {
+    Range(std::ops::Range<i64>),
+    /// Lists are read an item at a time, so the loop sees items pushed while it runs.
+    List(Rc<RefCell<Vec<Value>>>, usize),
+    /// The keys of a map or the characters of a string, taken when the loop starts.
+    Items(std::vec::IntoIter<Value>),
+    /// A struct with a `next` method. Each call returns an item, or unit when there are no more.
+    Struct(Value, Func),
+}
+
+/// How deeply calls may nest by default. A thread with a stack of
+/// [`parser::STACK_SIZE`] has room for them even in a debug build.
+pub const DEFAULT_CALL_LIMIT: usize = 5_000;
+
+pub struct Evaluator<W: Write> {
+    /// Functions and top-level `const` and `let` values.
+    globals: Rc<RefCell<Env>>,
+    env: Rc<RefCell<Env>>,
+    structs: HashMap<String, Rc<StructType>>,
+    /// Enum variants by name, as patterns refer to them.
+    variants: HashMap<String, (Rc<EnumType>, usize)>,
+    stdout: W,
+    /// Calls of user functions currently running.
+    depth: usize,
+    call_limit: usize,
+}
+
+impl<W: Write> Evaluator<W> {
+    /// `print` writes to `stdout`.
+    pub fn new(stdout: W) -> Self {
+        let globals: Rc<RefCell<Env>> = Rc::default();
+        let mut evaluator = Evaluator {
+            env: globals.clone(),
+            globals,
+            structs: HashMap::new(),
+            variants: HashMap::new(),
+            stdout,
+            depth: 0,
+            call_limit: DEFAULT_CALL_LIMIT,
+        };
+        evaluator.set("print".to_string(), |stdout, args| {
+            let text: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            syscall_stdout(stdout, &text.join(" "))?;
+            Ok(Value::Unit)
+        });
+        evaluator.set("len".to_string(), |_, args| {
+            let len = match args {
+                [Value::List(items)] => items.borrow().len(),
+                [Value::Map(entries)] => entries.borrow().len(),
+                [Value::String(value)] => value.chars().count(),
+                [value] => return Err(format!("`len` takes a list, map or string, found {}", value.type_name())),
+                _ => return Err(format!("`len` takes 1 argument(s) but {} were given", args.len())),
+            };
+            Ok(Value::Int(len as i64))
+        });
+        evaluator.set("push".to_string(), |_, args| match args {
+            [Value::List(items), value] => {
+                items.borrow_mut().push(value.clone());
+                Ok(Value::Unit)
+            }
+            [value, _] => Err(format!("`push` takes a list, found {}", value.type_name())),
+            _ => Err(format!("`push` takes 2 argument(s) but {} were given", args.len())),
+        });
+
+        evaluator
+    }
+
+    /// Defines the builtin function `key`.
+    pub fn set(&mut self, key: String, value: Builtin) {
+        let mut globals = self.globals.borrow_mut();
+        globals.vars.insert(key.clone(), Value::Func(Func::Builtin(key.clone(), value)));
+        globals.constants.insert(key);
+    }
+
+    pub fn stdout(&self) -> &W {
+        &self.stdout
+    }
+
+    /// Sets how deeply calls may nest. A deeper call is a runtime error.
+    pub fn with_call_limit(mut self, call_limit: usize) -> Self {
+        self.call_limit = call_limit;
+        self
+    }
+
+    /// Initialises the globals in order, then runs `main` and returns its value.
+    pub fn apply(&mut self, ast: parser::Program) -> Result<Value, RuntimeError> {
+        for global in self.eval_program(ast)? {
+            self.eval_global(&global)?;
+        }
+
+        let main = self.globals.borrow().get("main");
+        match &main {
+            Some(Value::Func(main @ Func::User(_))) => self.call(main, vec![], Span::default()),
+            _ => error("no `main` function".to_string(), Span::default()),
+        }
+    }
+
+    /// Defines the functions, structs and enum variants, and returns the
+    /// globals, which need them to be initialised.
+    fn eval_program(&mut self, ast: parser::Program) -> Result<Vec<parser::Global>, RuntimeError> {
+        let mut globals = Vec::new();
+        let mut structs = HashMap::new();
+        let mut impls = Vec::new();
+        let mut enums = Vec::new();
+        for item in ast {
+            match item {
+                parser::Item::FuncDef(func_def) => self.eval_funcdef(func_def),
+                parser::Item::Global(global) => globals.push(global),
+                parser::Item::Struct(struct_def) => {
+                    let ty = eval_struct_def(struct_def)?;
+                    if structs.contains_key(&ty.name.name) {
+                        return error(format!("struct `{}` is defined twice", ty.name.name), ty.name.span);
+                    }
+                    structs.insert(ty.name.name.clone(), ty);
+                }
+                parser::Item::Impl(impl_def) => impls.push(impl_def),
+                parser::Item::Enum(enum_def) => enums.push(enum_def),
+            }
+        }
+
+        // Variants are constants next to the functions, so they are defined after them.
+        for enum_def in enums {
+            self.eval_enum_def(enum_def)?;
+        }
+
+        // An `impl` may come before its struct.
+        for impl_def in impls {
+            let Some(ty) = structs.get_mut(&impl_def.name.name) else {
+                return error(format!("undefined struct `{}`", impl_def.name.name), impl_def.name.span);
+            };
+            for method in impl_def.methods {
+                let name = method.name.name.clone();
+                if ty.methods.contains_key(&name) {
+                    return error(format!("`{}` already has a method `{}`", ty.name.name, name), method.name.span);
+                }
+                let closure = Closure {
+                    span: method.name.span,
+                    name: Some(method.name),
+                    args: method.args,
+                    body: method.body,
+                    env: self.globals.clone(),
+                    method: true,
+                };
+                ty.methods.insert(name, Func::User(Rc::new(closure)));
+            }
+        }
+
+        self.structs = structs.into_iter().map(|(name, ty)| (name, Rc::new(ty))).collect();
+        Ok(globals)
+    }
+
+    fn eval_global(&mut self, ast: &parser::Global) -> Result<(), RuntimeError> {
+        let value = self.eval_expr(&ast.value)?;
+
+        let mut globals = self.globals.borrow_mut();
+        if globals.vars.contains_key(&ast.name.name) {
+            return error(format!("global `{}` is defined twice", ast.name.name), ast.name.span);
+        }
+        globals.vars.insert(ast.name.name.clone(), value);
+        if !ast.mutable {
+            globals.constants.insert(ast.name.name.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Defines each variant as a constant: a constructor function, or the
+    /// value itself if the variant has no fields.
+    fn eval_enum_def(&mut self, ast: parser::EnumDef) -> Result<(), RuntimeError> {
+        let ty = Rc::new(EnumType { name: ast.name, variants: ast.variants });
+
+        let mut globals = self.globals.borrow_mut();
+        for (i, variant) in ty.variants.iter().enumerate() {
+            let name = &variant.name.name;
+            if globals.vars.contains_key(name) {
+                return error(format!("`{}` is defined twice", name), variant.name.span);
+            }
+
+            let value = if variant.fields.is_empty() {
+                Value::Enum(Rc::new(EnumValue { ty: ty.clone(), variant: i, fields: Vec::new() }))
+            } else {
+                Value::Func(Func::Variant(ty.clone(), i))
+            };
+            globals.vars.insert(name.clone(), value);
+            globals.constants.insert(name.clone());
+            self.variants.insert(name.clone(), (ty.clone(), i));
+        }
+
+        Ok(())
+    }
+
+    /// Defines a top-level function. Like a constant, it cannot be reassigned.
+    fn eval_funcdef(&mut self, ast: parser::FuncDef) {
+        let name = ast.name.name.clone();
+        let closure = Closure {
+            span: ast.name.span,
+            name: Some(ast.name),
+            args: ast.args,
+            body: ast.body,
+            env: self.globals.clone(),
+            method: false,
+        };
+
+        let mut globals = self.globals.borrow_mut();
+        globals.vars.insert(name.clone(), Value::Func(Func::User(Rc::new(closure))));
+        globals.constants.insert(name);
+    }
+
+    fn call(&mut self, func: &Func, args: Vec<Value>, span: Span) -> Result<Value, RuntimeError> {
+        match func {
+            Func::Builtin(_, builtin) => builtin(&mut self.stdout, &args).or_else(|message| error(message, span)),
+            Func::Variant(ty, variant) => {
+                let name = &ty.variants[*variant].name;
+                let fields = ty.variants[*variant].fields.len();
+                if fields != args.len() {
+                    let message = format!("`{}` takes {} argument(s) but {} were given", name.name, fields, args.len());
+                    let label = Label::new(name.span, format!("`{}` defined here", name.name));
+                    return Err(RuntimeError { message, span, labels: vec![label] });
+                }
+
+                Ok(Value::Enum(Rc::new(EnumValue { ty: ty.clone(), variant: *variant, fields: args })))
+            }
+            Func::User(closure) => {
+                if closure.args.len() != args.len() {
+                    let (name, label) = match &closure.name {
+                        Some(name) => (format!("`{}`", name.name), format!("`{}` defined here", name.name)),
+                        None => ("the function".to_string(), "function defined here".to_string()),
+                    };
+                    let receiver = usize::from(closure.method);
+                    let message = format!(
+                        "{} takes {} argument(s) but {} were given",
+                        name,
+                        closure.args.len() - receiver,
+                        args.len() - receiver,
+                    );
+                    return Err(RuntimeError { message, span, labels: vec![Label::new(closure.span, label)] });
+                }
+
+                if self.depth >= self.call_limit {
+                    return error(format!("stack overflow: call depth exceeded {}", self.call_limit), span);
+                }
 
-        Evaluator { env }
+                let env = Env::child(&closure.env);
+                for (arg, value) in closure.args.iter().zip(args) {
+                    env.borrow_mut().vars.insert(arg.name.clone(), value);
+                }
+
+                let caller_env = std::mem::replace(&mut self.env, env);
+                self.depth += 1;
+                let flow = self.eval_statement_list(&closure.body);
+                self.depth -= 1;
+                self.env = caller_env;
+
+                match flow? {
+                    Flow::Break(span) => error("`break` outside of a loop".to_string(), span),
+                    Flow::Continue(span) => error("`continue` outside of a loop".to_string(), span),
+                    Flow::Return(value) => Ok(value),
+                    Flow::Normal => Ok(Value::Unit),
+                }
+            }
+        }
+    }
+
+    fn eval_statement_list(&mut self, ast: &parser::Block) -> Result<Flow, RuntimeError> {
+        for stmt in ast {
+            match self.eval_statement(stmt)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn eval_statement(&mut self, ast: &parser::Stmt) -> Result<Flow, RuntimeError> {
+        match &ast.kind {
+            StmtKind::Expr(expr) => {
+                self.eval_expr(expr)?;
+            }
+            StmtKind::Assign(ident, expr) => self.eval_assign(ident, expr)?,
+            StmtKind::SetIndex(target, index, value) => self.eval_set_index(target, index, value, ast.span)?,
+            StmtKind::SetField(target, field, value) => self.eval_set_field(target, field, value)?,
+            StmtKind::While(cond, body) => return self.eval_while(cond, body),
+            StmtKind::For(name, iterable, body) => return self.eval_for(name, iterable, body),
+            StmtKind::If(cond, then, otherwise) => {
+                if self.eval_cond(cond)? {
+                    return self.eval_statement_list(then);
+                } else if let Some(otherwise) = otherwise {
+                    return self.eval_statement_list(otherwise);
+                }
+            }
+            StmtKind::Break => return Ok(Flow::Break(ast.span)),
+            StmtKind::Continue => return Ok(Flow::Continue(ast.span)),
+            StmtKind::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.eval_expr(expr)?,
+                    None => Value::Unit,
+                };
+                return Ok(Flow::Return(value));
+            }
+        }
+
+        Ok(Flow::Normal)
     }
 
-    pub fn get(&self, key: &str) -> Option<fn(Vec<String>)->()> {
-        self.env.borrow().func_table.get(key).copied()
+    fn eval_assign(&mut self, ident: &parser::Ident, expr: &parser::Expr) -> Result<(), RuntimeError> {
+        let value = self.eval_expr(expr)?;
+        self.env.borrow_mut().assign(&ident.name, value).or_else(|message| error(message, ident.span))
     }
 
-    pub fn set(&mut self, key: String, value: fn(Vec<String>)->()) {
-        
-        self.env.borrow_mut().func_table.insert(key, value);
+    fn eval_set_index(&mut self, target: &parser::Expr, index: &parser::Expr, value: &parser::Expr, span: Span) -> Result<(), RuntimeError> {
+        let target = self.eval_expr(target)?;
+        let index = self.eval_expr(index)?;
+        let value = self.eval_expr(value)?;
+        set_index(&target, &index, value).or_else(|message| error(message, span))
+    }
+
+    fn eval_set_field(&mut self, target: &parser::Expr, field: &parser::Ident, value: &parser::Expr) -> Result<(), RuntimeError> {
+        let target = self.eval_expr(target)?;
+        let value = self.eval_expr(value)?;
+        set_field(&target, field, value)
+    }
+
+    fn eval_while(&mut self, cond: &parser::Expr, body: &parser::Block) -> Result<Flow, RuntimeError> {
+        while self.eval_cond(cond)? {
+            match self.eval_statement_list(body)? {
+                Flow::Normal | Flow::Continue(_) => {}
+                Flow::Break(_) => break,
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn eval_for(&mut self, name: &parser::Ident, iterable: &parser::Expr, body: &parser::Block) -> Result<Flow, RuntimeError> {
+        // The variable shadows, but never changes, a name bound outside the
+        // loop. Otherwise it keeps its last value, like names first assigned
+        // in the body.
+        let shadows = self.env.borrow().get(&name.name).is_some();
+        let mut last = None;
+        let mut iter = self.iter(iterable)?;
+        while let Some(item) = self.next_item(&mut iter, iterable.span)? {
+            // Each pass has its own variable, so closures made in the body keep theirs.
+            let env = Env::pass(&self.env, name.name.clone(), item);
+            let outer_env = std::mem::replace(&mut self.env, env);
+            let flow = self.eval_statement_list(body);
+            let env = std::mem::replace(&mut self.env, outer_env);
+            last = env.borrow().vars.get(&name.name).cloned();
+            match flow? {
+                Flow::Normal | Flow::Continue(_) => {}
+                Flow::Break(_) => break,
+                flow => return Ok(flow),
+            }
+        }
+
+        if let (false, Some(last)) = (shadows, last) {
+            self.env.borrow_mut().assign(&name.name, last).or_else(|message| error(message, name.span))?;
+        }
+        Ok(Flow::Normal)
     }
 
-    pub fn apply(&self, ast: parser::Program) {
-        self.eval_program(&ast);
+    fn eval_cond(&mut self, ast: &parser::Expr) -> Result<bool, RuntimeError> {
+        match self.eval_expr(ast)? {
+            Value::Bool(value) => Ok(value),
+            value => error(format!("expected a bool condition, found {}", value.type_name()), ast.span),
+        }
+    }
 
-        let main_func = self.get("main").unwrap();
-        main_func(vec![]);
+    fn eval_expr(&mut self, ast: &parser::Expr) -> Result<Value, RuntimeError> {
+        match &ast.kind {
+            ExprKind::Literal(literal) => Ok(eval_literal(literal)),
+            ExprKind::Ident(name) => self.eval_ident(name, ast.span),
+            ExprKind::Call(callee, args) => self.eval_call(callee, args, ast.span),
+            ExprKind::Index(target, index) => self.eval_index(target, index, ast.span),
+            ExprKind::List(items) => self.eval_list(items),
+            ExprKind::Map(entries) => self.eval_map(entries),
+            ExprKind::Field(target, field) => self.eval_field(target, field),
+            ExprKind::Struct(name, fields) => self.eval_struct(name, fields, ast.span),
+            ExprKind::Match(value, arms) => self.eval_match(value, arms, ast.span),
+            ExprKind::Lambda(args, body) => Ok(self.eval_lambda(args, body, ast.span)),
+            ExprKind::Unary(op, operand) => self.eval_unary(*op, operand, ast.span),
+            ExprKind::Binary(op, lhs, rhs) => self.eval_binary(*op, lhs, rhs, ast.span),
+            ExprKind::Range(start, end) => self.eval_range(start, end),
+        }
     }
 
-    fn eval_program(&self, ast: &parser::Program) {
-        for program in ast {
-            self.eval_funcdef(program);
+    fn eval_ident(&mut self, name: &parser::Ident, span: Span) -> Result<Value, RuntimeError> {
+        match self.env.borrow().get(&name.name) {
+            Some(value) => Ok(value),
+            None => error(format!("undefined variable `{}`", name.name), span),
         }
     }
 
-    fn eval_funcdef(&self, ast: &parser::FuncDef) {
-        let name = &ast.0;
-        let args = &ast.1;
-        let statement_list = &ast.2;
-        let func = move |statement_list| {self.eval_statement_list(statement_list)};
+    fn eval_index(&mut self, target: &parser::Expr, index: &parser::Expr, span: Span) -> Result<Value, RuntimeError> {
+        let target = self.eval_expr(target)?;
+        let index = self.eval_expr(index)?;
+        get_index(&target, &index).or_else(|message| error(message, span))
+    }
+
+    fn eval_list(&mut self, items: &[parser::Expr]) -> Result<Value, RuntimeError> {
+        let items = items.iter().map(|item| self.eval_expr(item)).collect::<Result<Vec<Value>, RuntimeError>>()?;
+        Ok(Value::List(Rc::new(RefCell::new(items))))
+    }
+
+    fn eval_field(&mut self, target: &parser::Expr, field: &parser::Ident) -> Result<Value, RuntimeError> {
+        let target = self.eval_expr(target)?;
+        get_field(&target, field)
+    }
+
+    fn eval_lambda(&mut self, args: &[parser::Ident], body: &parser::Block, span: Span) -> Value {
+        let closure = Closure {
+            name: None,
+            args: args.to_vec(),
+            body: body.clone(),
+            span,
+            env: self.env.clone(),
+            method: false,
+        };
+        Value::Func(Func::User(Rc::new(closure)))
+    }
+
+    fn eval_unary(&mut self, op: UnaryOp, operand: &parser::Expr, span: Span) -> Result<Value, RuntimeError> {
+        let value = self.eval_expr(operand)?;
+        eval_unary(op, value).or_else(|message| error(message, span))
+    }
 
-        self.set(name.value, func);
+    fn eval_binary(&mut self, op: BinOp, lhs: &parser::Expr, rhs: &parser::Expr, span: Span) -> Result<Value, RuntimeError> {
+        let lhs = self.eval_expr(lhs)?;
+        let rhs = self.eval_expr(rhs)?;
+        eval_binary(op, lhs, rhs).or_else(|message| error(message, span))
     }
 
-    fn eval_statement_list(&self, ast: &parser::StatList) {
+    fn eval_call(&mut self, callee: &parser::Expr, args: &[parser::Expr], span: Span) -> Result<Value, RuntimeError> {
+        // `target.name(...)` calls a method unless `name` is a field.
+        let (func, receiver) = match &callee.kind {
+            ExprKind::Ident(name) => match self.env.borrow().get(&name.name) {
+                Some(value) => (value, None),
+                None => return error(format!("undefined function `{}`", name.name), callee.span),
+            },
+            ExprKind::Field(target, name) => {
+                let target = self.eval_expr(target)?;
+                match method(&target, &name.name) {
+                    Some(method) => (Value::Func(method), Some(target)),
+                    None => (get_field(&target, name)?, None),
+                }
+            }
+            _ => (self.eval_expr(callee)?, None),
+        };
+        let Value::Func(func) = &func else {
+            return error(format!("cannot call a value of type {}", func.type_name()), callee.span);
+        };
+
+        let mut values: Vec<Value> = receiver.into_iter().collect();
+        for arg in args {
+            values.push(self.eval_expr(arg)?);
+        }
+        self.call(func, values, span)
+    }
 
+    fn eval_map(&mut self, entries: &[(parser::Expr, parser::Expr)]) -> Result<Value, RuntimeError> {
+        let mut map = BTreeMap::new();
+        for (key, value) in entries {
+            let name = match &self.eval_expr(key)? {
+                Value::String(name) => name.clone(),
+                found => return error(format!("map keys must be string, found {}", found.type_name()), key.span),
+            };
+            map.insert(name, self.eval_expr(value)?);
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    }
+
+    fn eval_struct(&mut self, name: &parser::Ident, fields: &[(parser::Ident, parser::Expr)], span: Span) -> Result<Value, RuntimeError> {
+        let Some(ty) = self.structs.get(&name.name).cloned() else {
+            return error(format!("undefined struct `{}`", name.name), name.span);
+        };
+
+        let mut values: Vec<Option<Value>> = vec![None; ty.fields.len()];
+        for (field, value) in fields {
+            let Some(i) = ty.fields.iter().position(|name| *name == field.name) else {
+                return Err(ty.no_field(field));
+            };
+            if values[i].is_some() {
+                return error(format!("field `{}` is given twice", field.name), field.span);
+            }
+            values[i] = Some(self.eval_expr(value)?);
+        }
+
+        let missing: Vec<String> = ty.fields.iter().zip(&values)
+            .filter(|(_, value)| value.is_none())
+            .map(|(name, _)| format!("`{}`", name))
+            .collect();
+        if !missing.is_empty() {
+            return error(format!("missing field(s) {} in `{}`", missing.join(", "), ty.name.name), span);
+        }
+
+        let fields = RefCell::new(values.into_iter().flatten().collect());
+        Ok(Value::Struct(Rc::new(Instance { ty, fields })))
+    }
+
+    fn eval_match(&mut self, value: &parser::Expr, arms: &[parser::Arm], span: Span) -> Result<Value, RuntimeError> {
+        let value = self.eval_expr(value)?;
+        for arm in arms {
+            let mut bindings = Vec::new();
+            if !self.match_pattern(&arm.pattern, &value, &mut bindings)? {
+                continue;
+            }
+
+            let env = Env::child(&self.env);
+            env.borrow_mut().vars.extend(bindings);
+            let outer_env = std::mem::replace(&mut self.env, env);
+            let result = self.eval_expr(&arm.body);
+            self.env = outer_env;
+            return result;
+        }
+
+        error(format!("no arm matches the value {}", value), span)
+    }
+
+    fn eval_range(&mut self, start: &parser::Expr, end: &parser::Expr) -> Result<Value, RuntimeError> {
+        let mut bounds = [0; 2];
+        for (bound, expr) in bounds.iter_mut().zip([start, end]) {
+            *bound = match self.eval_expr(expr)? {
+                Value::Int(value) => value,
+                found => return error(format!("range bounds must be int, found {}", found.type_name()), expr.span),
+            };
+        }
+        Ok(Value::Range(bounds[0], bounds[1]))
+    }
+
+    /// Starts a `for` loop over the value of `iterable`.
+    fn iter(&mut self, iterable: &parser::Expr) -> Result<Iter, RuntimeError> {
+        let value = self.eval_expr(iterable)?;
+        let iter = match &value {
+            Value::Range(start, end) => Iter::Range(*start..*end),
+            Value::List(items) => Iter::List(items.clone(), 0),
+            Value::Map(entries) => {
+                let keys: Vec<Value> = entries.borrow().keys().cloned().map(Value::String).collect();
+                Iter::Items(keys.into_iter())
+            }
+            Value::String(value) => {
+                let chars: Vec<Value> = value.chars().map(|c| Value::String(c.to_string())).collect();
+                Iter::Items(chars.into_iter())
+            }
+            _ => match method(&value, "next") {
+                Some(next) => Iter::Struct(value.clone(), next),
+                None => return error(format!("cannot iterate over a value of type {}", value.type_name()), iterable.span),
+            },
+        };
+
+        Ok(iter)
+    }
+
+    /// The next item of a `for` loop, or `None` when it is done.
+    fn next_item(&mut self, iter: &mut Iter, span: Span) -> Result<Option<Value>, RuntimeError> {
+        let item = match iter {
+            Iter::Range(range) => range.next().map(Value::Int),
+            Iter::List(items, i) => {
+                let item = items.borrow().get(*i).cloned();
+                *i += 1;
+                item
+            }
+            Iter::Items(items) => items.next(),
+            Iter::Struct(target, next) => match self.call(next, vec![target.clone()], span)? {
+                Value::Unit => None,
+                item => Some(item),
+            },
+        };
+
+        Ok(item)
+    }
+
+    /// Whether `value` matches `pattern`. The names the pattern binds are added to `bindings`.
+    fn match_pattern(&self, pattern: &parser::Pattern, value: &Value, bindings: &mut Vec<(String, Value)>) -> Result<bool, RuntimeError> {
+        let (name, fields) = match &pattern.kind {
+            parser::PatternKind::Literal(literal) => {
+                let literal = eval_literal(literal);
+                return Ok(eval_binary(BinOp::Eq, value.clone(), literal) == Ok(Value::Bool(true)));
+            }
+            parser::PatternKind::Wildcard => return Ok(true),
+            parser::PatternKind::Binding(name) if !self.variants.contains_key(&name.name) => {
+                bindings.push((name.name.clone(), value.clone()));
+                return Ok(true);
+            }
+            parser::PatternKind::Binding(name) => (name, &[][..]),
+            parser::PatternKind::Variant(name, fields) => (name, &fields[..]),
+        };
+
+        let Some((ty, variant)) = self.variants.get(&name.name) else {
+            return error(format!("undefined variant `{}`", name.name), name.span);
+        };
+        let expected = ty.variants[*variant].fields.len();
+        if expected != fields.len() {
+            return error(format!("`{}` has {} field(s) but the pattern has {}", name.name, expected, fields.len()), pattern.span);
+        }
+
+        match value {
+            Value::Enum(value) if Rc::ptr_eq(&value.ty, ty) && value.variant == *variant => {
+                for (field, value) in fields.iter().zip(&value.fields) {
+                    if !self.match_pattern(field, value, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+fn eval_unary(op: UnaryOp, value: Value) -> Result<Value, String> {
+    match (op, value) {
+        (UnaryOp::Neg, Value::Int(value)) => value.checked_neg().map(Value::Int).ok_or_else(|| "integer overflow".to_string()),
+        (UnaryOp::Neg, Value::Float(value)) => Ok(Value::Float(-value)),
+        (UnaryOp::Not, Value::Bool(value)) => Ok(Value::Bool(!value)),
+        (op, value) => Err(format!("cannot apply {:?} to {}", op, value.type_name())),
+    }
+}
+
+fn eval_binary(op: BinOp, lhs: Value, rhs: Value) -> Result<Value, String> {
+    let value = match (op, &lhs, &rhs) {
+        (BinOp::Add, Value::String(lhs), Value::String(rhs)) => Value::String(lhs.clone() + rhs),
+        (op, &Value::Int(lhs), &Value::Int(rhs)) => {
+            let value = match op {
+                BinOp::Add => lhs.checked_add(rhs),
+                BinOp::Sub => lhs.checked_sub(rhs),
+                BinOp::Mul => lhs.checked_mul(rhs),
+                BinOp::Div | BinOp::Mod if rhs == 0 => return Err("division by zero".to_string()),
+                BinOp::Div => lhs.checked_div(rhs),
+                BinOp::Mod => lhs.checked_rem(rhs),
+                _ => return Ok(Value::Bool(compare(op, lhs.cmp(&rhs)))),
+            };
+            Value::Int(value.ok_or("integer overflow")?)
+        }
+        (op, &Value::Int(lhs), &Value::Float(rhs)) => eval_float(op, lhs as f64, rhs),
+        (op, &Value::Float(lhs), &Value::Int(rhs)) => eval_float(op, lhs, rhs as f64),
+        (op, &Value::Float(lhs), &Value::Float(rhs)) => eval_float(op, lhs, rhs),
+        (op @ (BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge), Value::String(lhs), Value::String(rhs)) => {
+            Value::Bool(compare(op, lhs.cmp(rhs)))
+        }
+        (BinOp::Eq, lhs, rhs) => Value::Bool(lhs == rhs),
+        (BinOp::Ne, lhs, rhs) => Value::Bool(lhs != rhs),
+        (op, lhs, rhs) => return Err(format!("cannot apply {:?} to {} and {}", op, lhs.type_name(), rhs.type_name())),
+    };
+
+    Ok(value)
+}
+
+fn eval_literal(literal: &parser::Literal) -> Value {
+    match literal {
+        parser::Literal::Int(value) => Value::Int(*value),
+        parser::Literal::Float(value) => Value::Float(*value),
+        parser::Literal::String(value) => Value::String(value.clone()),
+        parser::Literal::Bool(value) => Value::Bool(*value),
+    }
+}
+
+fn eval_struct_def(ast: parser::StructDef) -> Result<StructType, RuntimeError> {
+    let mut fields: Vec<String> = Vec::new();
+    for field in ast.fields {
+        if fields.contains(&field.name) {
+            return error(format!("field `{}` is declared twice", field.name), field.span);
+        }
+        fields.push(field.name);
+    }
+
+    Ok(StructType { name: ast.name, fields, methods: HashMap::new() })
+}
+
+/// The method `name` of `target`, if it is a struct with such a method and no such field.
+fn method(target: &Value, name: &str) -> Option<Func> {
+    match target {
+        Value::Struct(instance) if !instance.ty.fields.iter().any(|field| field == name) => {
+            instance.ty.methods.get(name).cloned()
+        }
+        _ => None,
+    }
+}
+
+fn get_field(target: &Value, field: &parser::Ident) -> Result<Value, RuntimeError> {
+    let Value::Struct(instance) = target else {
+        return error(format!("cannot access field `{}` of a value of type {}", field.name, target.type_name()), field.span);
+    };
+
+    match instance.ty.fields.iter().position(|name| *name == field.name) {
+        Some(i) => Ok(instance.fields.borrow()[i].clone()),
+        None => Err(instance.ty.no_field(field)),
+    }
+}
+
+fn set_field(target: &Value, field: &parser::Ident, value: Value) -> Result<(), RuntimeError> {
+    let Value::Struct(instance) = target else {
+        return error(format!("cannot assign to field `{}` of a value of type {}", field.name, target.type_name()), field.span);
+    };
+
+    match instance.ty.fields.iter().position(|name| *name == field.name) {
+        Some(i) => {
+            instance.fields.borrow_mut()[i] = value;
+            Ok(())
+        }
+        None => Err(instance.ty.no_field(field)),
+    }
+}
+
+fn get_index(target: &Value, index: &Value) -> Result<Value, String> {
+    match (target, index) {
+        (Value::List(items), Value::Int(index)) => {
+            let items = items.borrow();
+            list_index(*index, items.len()).map(|i| items[i].clone())
+        }
+        (Value::List(_), index) => Err(format!("list indices must be int, found {}", index.type_name())),
+        (Value::Map(entries), Value::String(key)) => match entries.borrow().get(key) {
+            Some(value) => Ok(value.clone()),
+            None => Err(format!("key {:?} is not in the map", key)),
+        },
+        (Value::Map(_), key) => Err(format!("map keys must be string, found {}", key.type_name())),
+        (target, _) => Err(format!("cannot index a value of type {}", target.type_name())),
+    }
+}
+
+fn set_index(target: &Value, index: &Value, value: Value) -> Result<(), String> {
+    match (target, index) {
+        (Value::List(items), Value::Int(index)) => {
+            let mut items = items.borrow_mut();
+            let i = list_index(*index, items.len())?;
+            items[i] = value;
+        }
+        (Value::List(_), index) => return Err(format!("list indices must be int, found {}", index.type_name())),
+        (Value::Map(entries), Value::String(key)) => {
+            entries.borrow_mut().insert(key.clone(), value);
+        }
+        (Value::Map(_), key) => return Err(format!("map keys must be string, found {}", key.type_name())),
+        (target, _) => return Err(format!("cannot index a value of type {}", target.type_name())),
+    }
+
+    Ok(())
+}
+
+fn list_index(index: i64, len: usize) -> Result<usize, String> {
+    match usize::try_from(index) {
+        Ok(i) if i < len => Ok(i),
+        _ => Err(format!("index {} is out of bounds for a list of length {}", index, len)),
+    }
+}
+
+fn eval_float(op: BinOp, lhs: f64, rhs: f64) -> Value {
+    match op {
+        BinOp::Add => Value::Float(lhs + rhs),
+        BinOp::Sub => Value::Float(lhs - rhs),
+        BinOp::Mul => Value::Float(lhs * rhs),
+        BinOp::Div => Value::Float(lhs / rhs),
+        BinOp::Mod => Value::Float(lhs % rhs),
+        BinOp::Eq => Value::Bool(lhs == rhs),
+        BinOp::Ne => Value::Bool(lhs != rhs),
+        BinOp::Lt => Value::Bool(lhs < rhs),
+        BinOp::Le => Value::Bool(lhs <= rhs),
+        BinOp::Gt => Value::Bool(lhs > rhs),
+        BinOp::Ge => Value::Bool(lhs >= rhs),
     }
 }
 
-fn syscall_stdout(text: &str) {
+fn compare(op: BinOp, ordering: std::cmp::Ordering) -> bool {
+    match op {
+        BinOp::Eq => ordering.is_eq(),
+        BinOp::Ne => ordering.is_ne(),
+        BinOp::Lt => ordering.is_lt(),
+        BinOp::Le => ordering.is_le(),
+        BinOp::Gt => ordering.is_gt(),
+        _ => ordering.is_ge(),
+    }
+}
+
+fn syscall_stdout(stdout: &mut dyn Write, text: &str) -> Result<(), String> {
+    writeln!(stdout, "{}", text).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+use super::*;
+use super::super::scanner;
+
+    fn run(text: &str) -> (Result<Value, RuntimeError>, String) {
+        let ast = parser::Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+        let mut evaluator = Evaluator::new(Vec::new());
+        let result = evaluator.apply(ast);
+
+        (result, String::from_utf8(evaluator.stdout().clone()).unwrap())
+    }
+
+    /// Runs `test` on a thread with the stack the default limits are meant for.
+    fn with_stack(test: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new().stack_size(parser::STACK_SIZE).spawn(test).unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn eval_hello_world() {
+        let text = r#"
+            fn main() {
+                print("Hello World");
+                greet("RML", 6 * 7);
+            }
+
+            fn greet(name, answer) {
+                print("Hi", name, answer / 4, answer % 4 * 1.5);
+            }
+        "#;
+
+        assert_eq!(run(text), (Ok(Value::Unit), "Hello World\nHi RML 10 3.0\n".to_string()));
+    }
+
+    #[test]
+    fn eval_operators() {
+        let text = r#"
+            fn main() {
+                print(1 + 2 * 3 - -4 % 3, 7 / 2, 7.0 / 2, 10 - 3 - 2);
+                print(!(1 < 2) == false, "a" + "b" == "ab", "a" < "b", 1 != 1.0);
+            }
+        "#;
+
+        assert_eq!(run(text), (Ok(Value::Unit), "8 3 3.5 5\ntrue true true false\n".to_string()));
+    }
+
+    #[test]
+    fn eval_fizz_buzz() {
+        let text = r#"
+            fn main() {
+                fizzbuzz(1, 15)
+            }
+
+            fn fizzbuzz(start, end) {
+                i = start;
+                while(true){
+                    if (i > end) {
+                    break;
+                    }
+                    if(i % 15 == 0){
+                        print("FizzBuzz");
+                    }else if(i % 3 == 0){
+                        print("Fizz");
+                    }else if(i % 5 == 0){
+                        print("Buzz");
+                    }else {
+                        print(i);
+                    }
+                    i = i + 1;
+                }
+            }
+        "#;
+
+        let expected = "1\n2\nFizz\n4\nBuzz\nFizz\n7\n8\nFizz\nBuzz\n11\nFizz\n13\n14\nFizzBuzz\n";
+        assert_eq!(run(text), (Ok(Value::Unit), expected.to_string()));
+    }
+
+    #[test]
+    fn eval_return_and_recursion() {
+        let text = r#"
+            fn main() {
+                return fib(10) + sum(4);
+            }
 
-}
\ No newline at end of file
+            fn fib(n) {
+                if n < 2 { return n; }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn sum(n) {
+                total = 0;
+                while n > 0 {
+                    total = total + n;
+                    n = n - 1;
+                }
+                return total;
+            }
+        "#;
+
+        assert_eq!(run(text).0, Ok(Value::Int(65)));
+
+        let (result, _) = run("fn main() {\n    if 1 { print(1); }\n}");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 2, column 8: expected a bool condition, found int");
+
+        let (result, _) = run("fn main() {\n    break;\n}");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 2, column 5: `break` outside of a loop");
+    }
+
+    #[test]
+    fn eval_limits_call_depth() {
+        with_stack(|| {
+            // The default limit must be reached before the stack runs out.
+            let (result, _) = run("fn main() { return main(); }");
+            assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 1, column 20: stack overflow: call depth exceeded 5000");
+
+            let (result, _) = run("fn main() {\n    f = fn(g) { for i in 0..1 { while true { return match i { _ => [g(g)][0] }; } } };\n    return f(f);\n}");
+            assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 2, column 69: stack overflow: call depth exceeded 5000");
+
+            let (result, _) = run("fn f(n) { if n == 0 { return 0; } return f(n - 1) + 1; }\nfn main() { return f(4998); }");
+            assert_eq!(result, Ok(Value::Int(4998)));
+
+            // `main` is the first call, and the depth goes back down after an error.
+            let text = "fn f(n) { if n == 0 { return 0; } return f(n - 1) + 1; }\nfn main() { g = fn() { return f(2); }; print(f(1)); return g(); }";
+            let ast = parser::Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+            let mut evaluator = Evaluator::new(Vec::new()).with_call_limit(4);
+            let result = evaluator.apply(ast);
+            assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 1, column 42: stack overflow: call depth exceeded 4");
+            assert_eq!(String::from_utf8(evaluator.stdout().clone()).unwrap(), "1\n");
+            assert_eq!(evaluator.depth, 0);
+        });
+    }
+
+    #[test]
+    fn eval_globals() {
+        let text = r#"
+            const GREETING = "Hi " + NAME;
+            let calls = 0;
+
+            fn main() {
+                count();
+                count();
+                print(GREETING, calls, LIMIT);
+                return shadow(1);
+            }
+
+            const NAME = "RML";
+            let LIMIT = double(calls + 2);
+
+            fn count() {
+                calls = calls + 1;
+            }
+
+            fn double(n) {
+                return n * 2;
+            }
+
+            fn shadow(calls) {
+                calls = calls + 10;
+                return calls;
+            }
+        "#;
+        let (result, _) = run(text);
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 2, column 38: undefined variable `NAME`");
+
+        let text = text.replacen("const GREETING = \"Hi \" + NAME;", "", 1) + "const GREETING = \"Hi \" + NAME;";
+        assert_eq!(run(&text), (Ok(Value::Int(11)), "Hi RML 2 4\n".to_string()));
+
+        let (result, _) = run("const MAX = 1;\nfn main() {\n    MAX = 2;\n}");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 3, column 5: cannot assign to constant `MAX`");
+
+        let (result, _) = run("fn main() {\n    local = 1;\n    peek();\n}\nfn peek() { return local; }");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 5, column 20: undefined variable `local`");
+    }
+
+    #[test]
+    fn eval_errors_carry_spans() {
+        let text = "fn main() {\n    print(1);\n    print(2 / 0);\n}";
+        assert_eq!(run(text), (
+            Err(RuntimeError { message: "division by zero".to_string(), span: Span { start: 36, end: 41, line: 3, column: 11 }, labels: vec![] }),
+            "1\n".to_string(),
+        ));
+
+        let (result, _) = run("fn main() {\n    nothing(1);\n}\nfn nothing() {}");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 2, column 5: `nothing` takes 0 argument(s) but 1 were given");
+
+        let (result, _) = run("fn helper() {}");
+        assert_eq!(result.unwrap_err().message, "no `main` function");
+
+        let (result, _) = run("fn main() {\n    missing(2 / 0);\n}");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 2, column 5: undefined function `missing`");
+    }
+
+    #[test]
+    fn eval_closures() {
+        let text = r#"
+            fn main() {
+                counter = make_counter(10);
+                counter();
+                print(counter(), counter());
+
+                add = fn(a, b) { return a + b; };
+                print(apply(add, 2, 3), apply(fn(x, y) { return x * y; }, 2, 3), twice(fn(x) { return x + 1; })(5));
+                print(add, print, apply, add == add, fn() {} == fn() {});
+                return fn(x) { return fn(y) { return x - y; }; }(10)(3);
+            }
+
+            fn make_counter(start) {
+                count = start;
+                return fn() {
+                    count = count + 1;
+                    return count;
+                };
+            }
+
+            fn apply(f, a, b) {
+                return f(a, b);
+            }
+
+            fn twice(f) {
+                return fn(x) { return f(f(x)); };
+            }
+        "#;
+
+        assert_eq!(run(text), (
+            Ok(Value::Int(7)),
+            "12 13\n5 6 7\n<fn> <fn print> <fn apply> true false\n".to_string(),
+        ));
+
+        let (result, _) = run("fn main() {\n    f = fn(x) {};\n    f();\n}");
+        let error = result.unwrap_err();
+        assert_eq!(error.to_string(), "Runtime Error at line 3, column 5: the function takes 1 argument(s) but 0 were given");
+        assert_eq!(error.labels[0].message, "function defined here");
+
+        let (result, _) = run("fn main() {\n    x = 1;\n    x(2);\n}");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 3, column 5: cannot call a value of type int");
+
+        let (result, _) = run("fn main() {\n    main = 1;\n}");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 2, column 5: cannot assign to constant `main`");
+    }
+
+    #[test]
+    fn eval_structs() {
+        let text = r#"
+            impl Point {
+                fn norm2(self) {
+                    return self.x * self.x + self.y * self.y;
+                }
+
+                fn shift(self, dx) {
+                    self.x = self.x + dx;
+                    return self;
+                }
+            }
+
+            struct Point { x, y }
+            struct Named { name, f }
+
+            fn main() {
+                p = Point { y: 4, x: 3 };
+                q = p;
+                q.shift(1).shift(-1);
+                print(p, p.norm2(), p == Point { x: 3, y: 4 }, p == q);
+
+                n = Named { name: "a", f: fn(x) { return x * 2; } };
+                n.name = [p.x];
+                print(n.name, n.f(21));
+                return p.shift(2).x;
+            }
+        "#;
+
+        assert_eq!(run(text), (
+            Ok(Value::Int(5)),
+            "Point { x: 3, y: 4 } 25 true true\n[3] 42\n".to_string(),
+        ));
+
+        let program = "struct P { x }\nimpl P {\n    fn get(self) { return self.x; }\n}\n";
+        let errors = [
+            ("P { x: 1 }.y", "line 5, column 24: `P` has no field `y`"),
+            ("P { x: 1, y: 2 }", "line 5, column 23: `P` has no field `y`"),
+            ("P {}", "line 5, column 13: missing field(s) `x` in `P`"),
+            ("P { x: 1, x: 2 }", "line 5, column 23: field `x` is given twice"),
+            ("Q {}", "line 5, column 13: undefined struct `Q`"),
+            ("1.x", "line 5, column 15: cannot access field `x` of a value of type int"),
+            ("P { x: 1 }.get(2)", "line 5, column 13: `get` takes 0 argument(s) but 1 were given"),
+            ("P { x: P {} }", "line 5, column 20: missing field(s) `x` in `P`"),
+        ];
+        for (expr, message) in errors {
+            let (result, _) = run(&format!("{}fn main() {{ {}; }}", program, expr));
+            assert_eq!(result.unwrap_err().to_string(), format!("Runtime Error at {}", message), "{}", expr);
+        }
+
+        let (result, _) = run(&format!("{}fn main() {{\n    p = P {{ x: 1 }};\n    p.z = 2;\n}}", program));
+        let error = result.unwrap_err();
+        assert_eq!(error.to_string(), "Runtime Error at line 7, column 7: `P` has no field `z`");
+        assert_eq!(error.labels, vec![Label::new(Span { start: 7, end: 8, line: 1, column: 8 }, "`P` defined here")]);
+
+        let (result, _) = run("impl Q {}\nfn main() {}");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 1, column 6: undefined struct `Q`");
+    }
+
+    #[test]
+    fn eval_enums_and_match() {
+        let text = r#"
+            enum Shape { Circle(r), Rect(w, h), Empty }
+
+            fn area(shape) {
+                return match shape {
+                    Circle(r) => 3 * r * r,
+                    Rect(w, h) => w * h,
+                    Empty => 0,
+                };
+            }
+
+            fn describe(x) {
+                return match x {
+                    0 => "zero",
+                    -1 => "minus one",
+                    "a" => "letter",
+                    true => "yes",
+                    Rect(Circle(_), h) => h,
+                    n => n,
+                };
+            }
+
+            fn main() {
+                shapes = [Circle(2), Rect(3, 4), Empty];
+                print(area(shapes[0]), area(shapes[1]), area(shapes[2]));
+                print(describe(0), describe(-1), describe("a"), describe(true), describe(Rect(Circle(1), 5)), describe(7));
+                print(Rect(1, 2), Empty, Circle(1) == Circle(1), Circle(1) == Rect(1, 1));
+                return match area(Empty) { 1 => 1, _ => 2 };
+            }
+        "#;
+
+        assert_eq!(run(text), (
+            Ok(Value::Int(2)),
+            "12 12 0\nzero minus one letter yes 5 7\nRect(1, 2) Empty true false\n".to_string(),
+        ));
+
+        let program = "enum E { A(x), B }\n";
+        let errors = [
+            ("match 1 { 2 => 3 }", "line 2, column 13: no arm matches the value 1"),
+            ("match B { C(x) => 1 }", "line 2, column 23: undefined variant `C`"),
+            ("match B { A(x, y) => 1 }", "line 2, column 23: `A` has 1 field(s) but the pattern has 2"),
+            ("A(1, 2)", "line 2, column 13: `A` takes 1 argument(s) but 2 were given"),
+        ];
+        for (expr, message) in errors {
+            let (result, _) = run(&format!("{}fn main() {{ {}; }}", program, expr));
+            assert_eq!(result.unwrap_err().to_string(), format!("Runtime Error at {}", message), "{}", expr);
+        }
+
+        let (result, _) = run("enum E { A, A }\nfn main() {}");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 1, column 13: `A` is defined twice");
+    }
+
+    #[test]
+    fn eval_for_loops() {
+        let text = r#"
+            struct Countdown { n }
+
+            impl Countdown {
+                fn next(self) {
+                    if self.n == 0 {
+                        return;
+                    }
+                    self.n = self.n - 1;
+                    return self.n + 1;
+                }
+            }
+
+            fn main() {
+                total = 0;
+                for i in 0..10 {
+                    if i % 2 == 0 { continue; }
+                    if i > 7 { break; }
+                    total = total + i;
+                }
+
+                xs = [1, 2];
+                for x in xs {
+                    if x < 4 { push(xs, x + 2); }
+                }
+                print(total, i, xs, 1..3, (1..3) == (1..3));
+
+                for i in 3..1 { print("never"); }
+                for k in {"b": 1, "a": 2} { print(k); }
+                for c in "hé" { print(c); }
+                for n in Countdown { n: 3 } { print(n); }
+                return total;
+            }
+        "#;
+
+        assert_eq!(run(text), (
+            Ok(Value::Int(16)),
+            "16 9 [1, 2, 3, 4, 5] 1..3 true\na\nb\nh\né\n3\n2\n1\n".to_string(),
+        ));
+
+        let text = r#"
+            let i = 5;
+
+            fn main() {
+                for i in 0..3 {}
+                for print in 0..3 {}
+                fs = [];
+                for x in [1, 2] {
+                    push(fs, fn() { return x; });
+                }
+                for n in 0..3 {
+                    last = n;
+                    if n == 0 { first = n; }
+                    for m in 0..1 { inner = first + n; }
+                }
+                print(i, fs[0](), fs[1](), x, last, first, inner);
+            }
+        "#;
+
+        assert_eq!(run(text), (Ok(Value::Unit), "5 1 2 2 2 0 2\n".to_string()));
+
+        let program = "struct P {}\nfn f() { continue; }\n";
+        let errors = [
+            ("for x in 1 {}", "line 3, column 22: cannot iterate over a value of type int"),
+            ("p = P {}; for x in p {}", "line 3, column 32: cannot iterate over a value of type P"),
+            ("for x in 0..1.5 {}", "line 3, column 25: range bounds must be int, found float"),
+            ("f();", "line 2, column 10: `continue` outside of a loop"),
+        ];
+        for (stmt, message) in errors {
+            let (result, _) = run(&format!("{}fn main() {{ {} }}", program, stmt));
+            assert_eq!(result.unwrap_err().to_string(), format!("Runtime Error at {}", message), "{}", stmt);
+        }
+    }
+
+    #[test]
+    fn eval_collections() {
+        let text = r#"
+            fn main() {
+                xs = [1, "a", [2.5]];
+                ys = xs;
+                ys[0] = 10;
+                push(xs, {"k": true});
+                print(xs, len(xs), xs[2][0]);
+
+                m = {"b": 2, "a": 1};
+                m["c"] = m["a"] + m["b"];
+                print(m, len(m), len("héllo"), [] == [], [1] == [2]);
+                return xs[3]["k"];
+            }
+        "#;
+
+        assert_eq!(run(text), (
+            Ok(Value::Bool(true)),
+            "[10, \"a\", [2.5], {\"k\": true}] 4 2.5\n{\"a\": 1, \"b\": 2, \"c\": 3} 3 5 true false\n".to_string(),
+        ));
+
+        let errors = [
+            ("[1, 2, 3][3]", "line 1, column 13: index 3 is out of bounds for a list of length 3"),
+            ("[1][-1]", "line 1, column 13: index -1 is out of bounds for a list of length 1"),
+            ("[1][\"0\"]", "line 1, column 13: list indices must be int, found string"),
+            ("{\"a\": 1}[\"b\"]", "line 1, column 13: key \"b\" is not in the map"),
+            ("{1: 2}", "line 1, column 14: map keys must be string, found int"),
+            ("1[0]", "line 1, column 13: cannot index a value of type int"),
+            ("push(1, 2)", "line 1, column 13: `push` takes a list, found int"),
+        ];
+        for (expr, message) in errors {
+            let (result, _) = run(&format!("fn main() {{ {}; }}", expr));
+            assert_eq!(result.unwrap_err().to_string(), format!("Runtime Error at {}", message), "{}", expr);
+        }
+
+        let (result, _) = run("fn main() {\n    xs = [];\n    xs[0] = 1;\n}");
+        assert_eq!(result.unwrap_err().to_string(), "Runtime Error at line 3, column 5: index 0 is out of bounds for a list of length 0");
+    }
+
+    #[test]
+    fn eval_self_containing_collections() {
+        let text = r#"
+            fn main() {
+                xs = [1];
+                push(xs, xs);
+                ys = [1];
+                push(ys, ys);
+                print(xs, xs == xs, xs == ys, xs == [1, [1]]);
+
+                m = {"a": 1};
+                m["me"] = m;
+                n = {"a": 1};
+                n["me"] = n;
+                print(m, [m], m == m, m == n);
+            }
+        "#;
+
+        assert_eq!(run(text), (
+            Ok(Value::Unit),
+            "[1, [...]] true true false\n{\"a\": 1, \"me\": {...}} [{\"a\": 1, \"me\": {...}}] true true\n".to_string(),
+        ));
+    }
+
+    #[test]
+    fn eval_self_containing_structs() {
+        let text = r#"
+            struct P { me }
+
+            fn main() {
+                p = P { me: 0 };
+                p.me = p;
+                q = P { me: 0 };
+                q.me = [q];
+                r = P { me: 0 };
+                r.me = [r];
+                print(p, q, p == p, q == r, p == q);
+            }
+        "#;
+
+        assert_eq!(run(text), (
+            Ok(Value::Unit),
+            "P { me: P {...} } P { me: [P {...}] } true true false\n".to_string(),
+        ));
+    }
+
+    #[test]
+    fn eval_deeply_nested_values() {
+        // These run on the test thread, so printing, comparing and dropping
+        // must not recurse once per level.
+        let text = r#"
+            struct Box { inner }
+            enum Option { Some(x), None }
+
+            fn wrap(g) { return fn() { return g; }; }
+
+            fn main() {
+                xs = [];
+                ys = [];
+                m = {};
+                b = Box { inner: 0 };
+                o = None;
+                f = 0;
+                for i in 0..50000 {
+                    xs = [xs];
+                    ys = [ys];
+                    m = {"m": m};
+                    b = Box { inner: b };
+                    o = Some(o);
+                    f = wrap(f);
+                }
+                print(xs == ys, xs == [ys], m == {"m": m}, b == b, o == Some(o));
+                print(xs, m, b, o);
+            }
+        "#;
+
+        let (result, stdout) = run(text);
+        assert_eq!(result, Ok(Value::Unit));
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines[0], "true false false true false");
+        let expected = [
+            format!("{}{}", "[".repeat(50_001), "]".repeat(50_001)),
+            format!("{}{{}}{}", "{\"m\": ".repeat(50_000), "}".repeat(50_000)),
+            format!("{}0{}", "Box { inner: ".repeat(50_001), " }".repeat(50_001)),
+            format!("{}None{}", "Some(".repeat(50_000), ")".repeat(50_000)),
+        ];
+        assert_eq!(lines[1], expected.join(" "));
+    }
+}
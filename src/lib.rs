@@ -0,0 +1,8 @@
+pub mod scanner;
+pub mod parser;
+pub mod evaluator;
+pub mod diagnostics;
+pub mod printer;
+pub mod dump;
+pub mod visit;
+pub mod check;
@@ -1,10 +1,101 @@
-mod scanner;
-mod parser;
+use std::io::IsTerminal;
+
+use rml_mini::{check, diagnostics, dump, evaluator, parser, scanner};
+
+const USAGE: &str = "usage: rml-mini [--tokens | --ast] [--format json|sexp] [FILE]";
+
+/// Without `--tokens` or `--ast` the program is run.
+enum Mode {
+    Run,
+    Tokens,
+    Ast,
+}
+
+enum Format {
+    Json,
+    Sexp,
+}
 
 fn main() {
-    let text = r#"fn main2() { print("test"); }"#;
-    let scanner = scanner::Scanner::new(text);
-    let mut parser = parser::Parser::new(scanner);
-    let ast = parser.parse();
-    println!("{:?}", ast);
+    // Parsing and the later passes recurse as deeply as the program nests.
+    let run = std::thread::Builder::new().stack_size(parser::STACK_SIZE).spawn(run).unwrap();
+    if run.join().is_err() {
+        std::process::exit(101);
+    }
+}
+
+fn run() {
+    let mut mode = Mode::Run;
+    let mut format = Format::Json;
+    let mut path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tokens" => mode = Mode::Tokens,
+            "--ast" => mode = Mode::Ast,
+            "--format" => match args.next().as_deref() {
+                Some("json") => format = Format::Json,
+                Some("sexp") => format = Format::Sexp,
+                _ => exit_with(USAGE),
+            },
+            _ if arg.starts_with('-') || path.is_some() => exit_with(USAGE),
+            _ => path = Some(arg),
+        }
+    }
+
+    let (file_name, text) = match path {
+        Some(path) => match std::fs::read_to_string(&path) {
+            Ok(text) => (path, text),
+            Err(err) => exit_with(&format!("error: cannot read {}: {}", path, err)),
+        },
+        None => ("<example>".to_string(), r#"fn main() { print("test"); }"#.to_string()),
+    };
+
+    let color = std::io::stdout().is_terminal() && std::io::stderr().is_terminal();
+    let report = |diagnostic: diagnostics::Diagnostic| {
+        eprintln!("{}", diagnostics::render(&diagnostic, &file_name, &text, color));
+    };
+
+    let parse = || {
+        let scanner = scanner::Scanner::new(&text);
+        let mut parser = parser::Parser::new(scanner);
+        match parser.parse() {
+            Ok(ast) => {
+                check::check(&ast).into_iter().for_each(report);
+                ast
+            }
+            Err(errors) => {
+                errors.into_iter().map(diagnostics::Diagnostic::from).for_each(report);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let node = match mode {
+        Mode::Run => {
+            let mut evaluator = evaluator::Evaluator::new(std::io::stdout());
+            if let Err(error) = evaluator.apply(parse()) {
+                report(error.into());
+                std::process::exit(1);
+            }
+            return;
+        }
+        Mode::Tokens => {
+            // Tokens the lexer could not read are dumped as `Error` tokens.
+            let tokens: Vec<scanner::Token> = scanner::Lexer::new(&text).collect();
+            dump::tokens(&tokens)
+        }
+        Mode::Ast => dump::program(&parse()),
+    };
+
+    match format {
+        Format::Json => println!("{}", node.to_json()),
+        Format::Sexp => println!("{}", node.to_sexp()),
+    }
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2);
 }
@@ -1,25 +1,288 @@
+use std::fmt;
+
+use super::diagnostics::Label;
 use super::scanner;
+use super::scanner::Span;
+
+pub type Program = Vec<Item>;
+pub type Block = Vec<Stmt>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    FuncDef(FuncDef),
+    Global(Global),
+    Struct(StructDef),
+    Impl(Impl),
+    Enum(EnumDef),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncDef {
+    pub name: Ident,
+    pub args: Vec<Ident>,
+    pub body: Block,
+    /// Text of the `///` comments before `fn`.
+    pub doc: Option<String>,
+    pub span: Span,
+}
+
+/// A top-level `const NAME = value;`, or `let NAME = value;` if `mutable`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: Ident,
+    pub value: Expr,
+    pub mutable: bool,
+    pub doc: Option<String>,
+    pub span: Span,
+}
+
+/// `struct Name { field, ... }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDef {
+    pub name: Ident,
+    pub fields: Vec<Ident>,
+    pub doc: Option<String>,
+    pub span: Span,
+}
+
+/// `impl Name { ... }`, the methods of a struct. Each method takes `self`
+/// as its first argument.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Impl {
+    pub name: Ident,
+    pub methods: Vec<FuncDef>,
+    pub span: Span,
+}
+
+/// `enum Name { Variant(field, ...), ... }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDef {
+    pub name: Ident,
+    pub variants: Vec<Variant>,
+    pub doc: Option<String>,
+    pub span: Span,
+}
+
+/// `Name(field, ...)`, or just `Name` if it has no fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: Ident,
+    pub fields: Vec<Ident>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Expr(Expr),
+    Assign(Ident, Expr),
+    /// `collection[index] = value;`
+    SetIndex(Box<Expr>, Box<Expr>, Expr),
+    /// `target.field = value;`
+    SetField(Box<Expr>, Ident, Expr),
+    While(Expr, Block),
+    /// `for name in iterable { body }`
+    For(Ident, Expr, Block),
+    /// `else if` is an `If` statement alone in the else block.
+    If(Expr, Block, Option<Block>),
+    Break,
+    Continue,
+    Return(Option<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Literal(Literal),
+    Ident(Ident),
+    Call(Box<Expr>, Vec<Expr>),
+    /// `collection[index]`
+    Index(Box<Expr>, Box<Expr>),
+    List(Vec<Expr>),
+    /// `{key: value, ...}`
+    Map(Vec<(Expr, Expr)>),
+    /// `target.field`
+    Field(Box<Expr>, Ident),
+    /// `Name { field: value, ... }`
+    Struct(Ident, Vec<(Ident, Expr)>),
+    /// `match value { pattern => expr, ... }`
+    Match(Box<Expr>, Vec<Arm>),
+    /// `fn(args) { body }`, a function value.
+    Lambda(Vec<Ident>, Block),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    /// `start..end`, the integers from `start` up to but not including `end`.
+    Range(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub body: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    Literal(Literal),
+    /// `_`
+    Wildcard,
+    /// `name` matches anything and binds it, unless it is a variant without fields.
+    Binding(Ident),
+    /// `Variant(pattern, ...)`
+    Variant(Ident, Vec<Pattern>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+    /// Other places in the source that explain the error.
+    pub labels: Vec<Label>,
+    pub help: Option<String>,
+}
+
+impl ParseError {
+    fn new(message: String, span: Span) -> Self {
+        ParseError { message, span, labels: Vec::new(), help: None }
+    }
+
+    fn with_label(mut self, span: Span, message: String) -> Self {
+        self.labels.push(Label::new(span, message));
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Syntax Error at line {}, column {}: {}", self.span.line, self.span.column, self.message)
+    }
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+/// How deeply blocks, expressions and operator chains may nest by default.
+/// The parser and every later pass recurse once per level, so at this depth
+/// they need a thread with a stack of [`STACK_SIZE`].
+pub const DEFAULT_NESTING_LIMIT: usize = 256;
+
+/// Stack size for a thread that parses and runs programs within the default
+/// limits, with room for a debug build.
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// Tokens that begin a top-level item.
+const ITEM_STARTS: [scanner::TokenKind; 6] = [
+    scanner::TokenKind::FuncDef,
+    scanner::TokenKind::Struct,
+    scanner::TokenKind::Impl,
+    scanner::TokenKind::Enum,
+    scanner::TokenKind::Const,
+    scanner::TokenKind::Let,
+];
 
-pub type Program = Vec<FuncDef>;
-pub type FuncDef = (scanner::Token, Vec<scanner::Token>, FuncArgs, StatList);
-pub type FuncArgs = Vec<scanner::Token>;
-pub type StatList = Vec<Statement>;
-pub type Statement = Vec<CallFunc>;
-pub type CallFunc = Vec<scanner::Token>;
-pub type Factor = Vec<scanner::Token>;
-pub type Literal = Vec<scanner::Token>;
 #[derive(Debug)]
-pub struct Parser {
-    scanner: scanner::Scanner
+pub struct Parser<'a> {
+    scanner: scanner::Scanner<'a>,
+    /// Span of the last token taken, where the node being parsed ends.
+    prev_span: Span,
+    errors: Vec<ParseError>,
+    /// Nesting levels currently open, and how many may be. The parser
+    /// recurses once per level, so this bounds its stack use.
+    depth: usize,
+    nesting_limit: usize,
+    /// The deepest level the expression being parsed reaches. Operator chains
+    /// such as `1 + 2 + 3`, which is `(1 + 2) + 3`, grow downwards without
+    /// the parser recursing, so they are measured with this instead.
+    reached: usize,
+    /// In an `if` or `while` condition `Name {}` is a name followed by the
+    /// body, not a struct literal.
+    in_condition: bool,
 }
 
-impl Parser {
-    pub fn new(scanner: scanner::Scanner) -> Parser {
-        Parser { scanner }
+impl<'a> Parser<'a> {
+    pub fn new(scanner: scanner::Scanner<'a>) -> Self {
+        Parser { scanner, prev_span: Span::default(), errors: Vec::new(), depth: 0, nesting_limit: DEFAULT_NESTING_LIMIT, reached: 0, in_condition: false }
     }
 
-    pub fn parse(&mut self) -> Program {
-        self.program()
+    /// Sets how deeply blocks, expressions and operator chains may nest.
+    /// Deeper input is a syntax error.
+    pub fn with_nesting_limit(mut self, nesting_limit: usize) -> Self {
+        self.nesting_limit = nesting_limit;
+        self
+    }
+
+    /// Parses the whole input. On failure every lexical and syntax error is
+    /// returned, in source order.
+    pub fn parse(&mut self) -> Result<Program, Vec<ParseError>> {
+        let program = self.program();
+
+        for lex_error in self.scanner.errors() {
+            let error = ParseError::new(lex_error.message.clone(), lex_error.span);
+            if !self.errors.contains(&error) {
+                self.errors.push(error);
+            }
+        }
+
+        if self.errors.is_empty() {
+            return Ok(program);
+        }
+
+        // Only the first of several errors at one spot is worth reporting.
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.sort_by_key(|error| error.span.start);
+        errors.dedup_by_key(|error| error.span.start);
+        Err(errors)
     }
 
     fn is_match(&self, kind: scanner::TokenKind) -> bool {
@@ -27,148 +290,717 @@ impl Parser {
         token.kind == kind
     }
 
-    fn take(&mut self, kind: Vec<scanner::TokenKind>) -> scanner::Token {
+    fn take(&mut self, kind: Vec<scanner::TokenKind>) -> ParseResult<scanner::Token> {
         let token = self.scanner.peek();
 
-        if self.scanner.is_not_end() {
-            self.scanner.next();
+        if !kind.contains(&token.kind) {
+            let expected: Vec<String> = kind.iter().map(|kind| kind.to_string()).collect();
+            return Err(self.unexpected(&expected.join(" or ")));
         }
 
-        let mut result = false;
-        for k in kind {
-            if token.kind == k {
-                result = true;
+        self.scanner.next();
+        self.prev_span = token.span;
+        Ok(token)
+    }
+
+    /// Takes the token closing the delimiter `open`. If it is missing the
+    /// error also points at `open`.
+    fn close(&mut self, kind: scanner::TokenKind, open: &scanner::Token) -> ParseResult<scanner::Token> {
+        self.take(vec![kind]).map_err(|error| error.with_label(open.span, format!("unclosed `{}`", open.value)))
+    }
+
+    /// Runs `parse` one nesting level deeper, failing if that is past the limit.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.depth >= self.nesting_limit {
+            return Err(self.too_deep());
+        }
+
+        self.depth += 1;
+        self.reached = self.reached.max(self.depth);
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Runs `parse` for an operator chain, which starts out at the current depth.
+    fn chain<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        let outer = std::mem::replace(&mut self.reached, self.depth);
+        let result = parse(self);
+        self.reached = self.reached.max(outer);
+        result
+    }
+
+    /// Moves the chain parsed so far one level down, under the operator at the
+    /// current token, failing if that is past the limit.
+    fn link(&mut self) -> ParseResult<()> {
+        if self.reached >= self.nesting_limit {
+            return Err(self.too_deep());
+        }
+
+        self.reached += 1;
+        Ok(())
+    }
+
+    fn too_deep(&self) -> ParseError {
+        let message = format!("nesting is deeper than the limit of {}", self.nesting_limit);
+        ParseError::new(message, self.scanner.peek().span)
+    }
+
+    /// Error for the current token, which is not what the grammar `expected`.
+    fn unexpected(&self, expected: &str) -> ParseError {
+        let token = self.scanner.peek();
+
+        // Tokens the scanner could not read already come with a better message.
+        if token.kind == scanner::TokenKind::Error {
+            let lex_error = self.scanner.errors().iter()
+                .find(|error| token.span.start <= error.span.start && error.span.start < token.span.end);
+            if let Some(lex_error) = lex_error {
+                return ParseError::new(lex_error.message.clone(), lex_error.span);
             }
         }
 
-        if !result {
-            panic!("Syntax Error: expect.kind={:?}, actual.kind={:?}, token.value={:?}", kind, token.kind, token.value);
+        let found = match token.kind {
+            scanner::TokenKind::Eof | scanner::TokenKind::String => token.kind.to_string(),
+            _ => format!("`{}`", token.value),
+        };
+        ParseError::new(format!("expected {}, found {}", expected, found), token.span)
+    }
+
+    /// `fn name`, `struct`, `impl` and `enum` start a definition; `fn(` starts a function expression.
+    fn is_definition(&mut self) -> bool {
+        match self.scanner.peek().kind {
+            scanner::TokenKind::FuncDef => self.scanner.peek_nth(1).kind == scanner::TokenKind::Ident,
+            scanner::TokenKind::Struct | scanner::TokenKind::Impl | scanner::TokenKind::Enum => true,
+            _ => false,
+        }
+    }
+
+    /// Skips the rest of a broken statement: up to and including the next `;`,
+    /// or up to the next `}` or definition.
+    fn synchronize(&mut self) {
+        loop {
+            match self.scanner.peek().kind {
+                scanner::TokenKind::Semicolon => {
+                    self.scanner.next();
+                    return;
+                }
+                scanner::TokenKind::End | scanner::TokenKind::Eof => return,
+                _ if self.is_definition() => return,
+                _ => {
+                    self.scanner.next();
+                }
+            }
         }
+    }
 
-        token
+    fn ident(&mut self) -> ParseResult<Ident> {
+        let token = self.take(vec![scanner::TokenKind::Ident])?;
+        Ok(Ident { name: token.value, span: token.span })
+    }
+
+    fn is_item_start(&self) -> bool {
+        ITEM_STARTS.contains(&self.scanner.peek().kind)
     }
 
     fn program(&mut self) -> Program {
         let mut program: Program = Vec::new();
-        while self.is_match(scanner::TokenKind::FuncDef) {
-            program.push(self.func_def());
+        while !self.is_match(scanner::TokenKind::Eof) {
+            if !self.is_item_start() {
+                let mut error = self.unexpected("an item");
+                error.help = Some("items are `fn`, `struct`, `impl`, `enum`, `const` and `let` definitions".to_string());
+                self.errors.push(error);
+                self.scanner.next();
+            } else {
+                match self.item() {
+                    Ok(item) => {
+                        program.push(item);
+                        continue;
+                    }
+                    Err(error) => self.errors.push(error),
+                }
+            }
+
+            while !self.is_item_start() && !self.is_match(scanner::TokenKind::Eof) {
+                self.scanner.next();
+            }
         }
 
         program
     }
 
-    fn func_def(&mut self) -> FuncDef {
-        let func_def = self.take(vec![scanner::TokenKind::FuncDef]);
-        let ident = self.take(vec![scanner::TokenKind::Ident]);
-        self.take(vec![scanner::TokenKind::ParenthesOpen]);
-        let func_args: FuncArgs = self.func_args();
-        self.take(vec![scanner::TokenKind::ParenthesClose]);
-        self.take(vec![scanner::TokenKind::Begin]);
-        let stat_list: StatList = self.stat_list();
-        self.take(vec![scanner::TokenKind::End]);
+    fn item(&mut self) -> ParseResult<Item> {
+        match self.scanner.peek().kind {
+            scanner::TokenKind::FuncDef => Ok(Item::FuncDef(self.func_def()?)),
+            scanner::TokenKind::Struct => Ok(Item::Struct(self.struct_def()?)),
+            scanner::TokenKind::Impl => Ok(Item::Impl(self.impl_def()?)),
+            scanner::TokenKind::Enum => Ok(Item::Enum(self.enum_def()?)),
+            _ => Ok(Item::Global(self.global()?)),
+        }
+    }
+
+    fn struct_def(&mut self) -> ParseResult<StructDef> {
+        let keyword = self.take(vec![scanner::TokenKind::Struct])?;
+        let name = self.ident()?;
+        let open = self.take(vec![scanner::TokenKind::Begin])?;
+        let mut fields = Vec::new();
+
+        while !self.is_match(scanner::TokenKind::End) {
+            fields.push(self.ident()?);
+            if !self.is_match(scanner::TokenKind::Comma) {
+                break;
+            }
+            self.take(vec![scanner::TokenKind::Comma])?;
+        }
+        self.close(scanner::TokenKind::End, &open)?;
 
-        (
-            func_def,
-            vec![ident],
-            func_args,
-            stat_list,
-        )
+        Ok(StructDef { name, fields, doc: keyword.doc_comment(), span: keyword.span.to(self.prev_span) })
     }
 
-    fn func_args(&mut self) -> FuncArgs {
-        let mut func_args: FuncArgs = Vec::new();
-        
-        while self.is_match(scanner::TokenKind::Ident) {
-            func_args.push(self.take(vec![scanner::TokenKind::Ident]));
-            if self.is_match(scanner::TokenKind::Comma) {
-                self.take(vec![scanner::TokenKind::Comma]);
+    fn enum_def(&mut self) -> ParseResult<EnumDef> {
+        let keyword = self.take(vec![scanner::TokenKind::Enum])?;
+        let name = self.ident()?;
+        let open = self.take(vec![scanner::TokenKind::Begin])?;
+        let mut variants = Vec::new();
+
+        while !self.is_match(scanner::TokenKind::End) {
+            let name = self.ident()?;
+            let mut fields = Vec::new();
+            if self.is_match(scanner::TokenKind::ParenthesOpen) {
+                let open = self.take(vec![scanner::TokenKind::ParenthesOpen])?;
+                fields = self.func_args()?;
+                self.close(scanner::TokenKind::ParenthesClose, &open)?;
             }
+            variants.push(Variant { span: name.span.to(self.prev_span), name, fields });
+
+            if !self.is_match(scanner::TokenKind::Comma) {
+                break;
+            }
+            self.take(vec![scanner::TokenKind::Comma])?;
         }
+        self.close(scanner::TokenKind::End, &open)?;
 
-        func_args
+        Ok(EnumDef { name, variants, doc: keyword.doc_comment(), span: keyword.span.to(self.prev_span) })
     }
 
-    fn stat_list(&mut self) -> StatList {
-        let mut stat_list: StatList = Vec::new();
-        while self.is_match(scanner::TokenKind::Ident) {
-            stat_list.push(self.statement());
+    fn impl_def(&mut self) -> ParseResult<Impl> {
+        let keyword = self.take(vec![scanner::TokenKind::Impl])?;
+        let name = self.ident()?;
+        let open = self.take(vec![scanner::TokenKind::Begin])?;
+        let mut methods = Vec::new();
+
+        while self.is_match(scanner::TokenKind::FuncDef) {
+            let method = self.func_def()?;
+            if method.args.first().is_none_or(|arg| arg.name != "self") {
+                let message = format!("method `{}` must take `self` as its first argument", method.name.name);
+                return Err(ParseError::new(message, method.name.span));
+            }
+            methods.push(method);
+        }
+        self.close(scanner::TokenKind::End, &open)?;
+
+        Ok(Impl { name, methods, span: keyword.span.to(self.prev_span) })
+    }
+
+    fn global(&mut self) -> ParseResult<Global> {
+        let keyword = self.take(vec![scanner::TokenKind::Const, scanner::TokenKind::Let])?;
+        let name = self.ident()?;
+        self.take(vec![scanner::TokenKind::Assign])?;
+        let value = self.expr()?;
+        self.semicolon()?;
+
+        Ok(Global {
+            name,
+            value,
+            mutable: keyword.kind == scanner::TokenKind::Let,
+            doc: keyword.doc_comment(),
+            span: keyword.span.to(self.prev_span),
+        })
+    }
+
+    fn func_def(&mut self) -> ParseResult<FuncDef> {
+        let func_def = self.take(vec![scanner::TokenKind::FuncDef])?;
+        let name = self.ident()?;
+        let open = self.take(vec![scanner::TokenKind::ParenthesOpen])?;
+        let args = self.func_args()?;
+        self.close(scanner::TokenKind::ParenthesClose, &open)?;
+        let body = self.block()?;
+
+        Ok(FuncDef { name, args, body, doc: func_def.doc_comment(), span: func_def.span.to(self.prev_span) })
+    }
+
+    fn func_args(&mut self) -> ParseResult<Vec<Ident>> {
+        let mut func_args = Vec::new();
+
+        while !self.is_match(scanner::TokenKind::ParenthesClose) {
+            func_args.push(self.ident()?);
+            if !self.is_match(scanner::TokenKind::Comma) {
+                break;
+            }
+            self.take(vec![scanner::TokenKind::Comma])?;
+        }
+
+        Ok(func_args)
+    }
+
+    fn block(&mut self) -> ParseResult<Block> {
+        let open = self.take(vec![scanner::TokenKind::Begin])?;
+        let stat_list = self.nested(|parser| Ok(parser.stat_list()))?;
+        self.close(scanner::TokenKind::End, &open)?;
+
+        Ok(stat_list)
+    }
+
+    /// Statements up to the closing `}`. Broken statements are recorded and skipped.
+    fn stat_list(&mut self) -> Block {
+        let mut stat_list = Vec::new();
+        while ![scanner::TokenKind::End, scanner::TokenKind::Eof].contains(&self.scanner.peek().kind) && !self.is_definition() {
+            match self.statement() {
+                Ok(statement) => stat_list.push(statement),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize();
+                }
+            }
         }
 
         stat_list
     }
 
-    fn statement(&mut self) -> Statement {
-        let mut statement: Statement = vec!();
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        let start = self.scanner.peek().span;
 
-        if self.is_match(scanner::TokenKind::While) {
-            statement.push(self.call_while());
+        let kind = match self.scanner.peek().kind {
+            scanner::TokenKind::While => self.call_while()?,
+            scanner::TokenKind::For => self.call_for()?,
+            scanner::TokenKind::If => self.call_if()?,
+            scanner::TokenKind::Break => {
+                self.take(vec![scanner::TokenKind::Break])?;
+                self.semicolon()?;
+                StmtKind::Break
+            }
+            scanner::TokenKind::Continue => {
+                self.take(vec![scanner::TokenKind::Continue])?;
+                self.semicolon()?;
+                StmtKind::Continue
+            }
+            scanner::TokenKind::Return => self.call_return()?,
+            scanner::TokenKind::Ident if self.scanner.peek_nth(1).kind == scanner::TokenKind::Assign => self.assign()?,
+            _ => {
+                let expr = self.expr()?;
+                if self.is_match(scanner::TokenKind::Assign) {
+                    self.assign_to(expr)?
+                } else {
+                    self.semicolon()?;
+                    StmtKind::Expr(expr)
+                }
+            }
+        };
+
+        Ok(Stmt { kind, span: start.to(self.prev_span) })
+    }
+
+    /// Takes the `;` ending a statement. It may be left out before `}`.
+    fn semicolon(&mut self) -> ParseResult<()> {
+        if !self.is_match(scanner::TokenKind::End) {
+            let statement_end = self.prev_span;
+            self.take(vec![scanner::TokenKind::Semicolon])
+                .map_err(|error| error.with_label(statement_end, "expected `;` after this".to_string()))?;
         }
 
-        let name = self.take(vec![scanner::TokenKind::Ident]);
-        statement.push(self.call_func(name));
-        self.take(vec![scanner::TokenKind::Semicolon]);
+        Ok(())
+    }
 
-        statement
+    fn assign(&mut self) -> ParseResult<StmtKind> {
+        let name = self.ident()?;
+        self.take(vec![scanner::TokenKind::Assign])?;
+        let value = self.expr()?;
+        self.semicolon()?;
+
+        Ok(StmtKind::Assign(name, value))
     }
 
-    fn call_func(&mut self, name: scanner::Token) -> CallFunc {
-        let mut call_func: CallFunc = vec!(); 
+    /// `collection[index] = value;` or `target.field = value;`.
+    fn assign_to(&mut self, target: Expr) -> ParseResult<StmtKind> {
+        if !matches!(target.kind, ExprKind::Index(..) | ExprKind::Field(..)) {
+            return Err(ParseError::new("cannot assign to this expression".to_string(), target.span));
+        }
+
+        self.take(vec![scanner::TokenKind::Assign])?;
+        let value = self.expr()?;
+        self.semicolon()?;
 
-        call_func.push(scanner::Token {kind: scanner::TokenKind::CallFunc, value: "".to_string()});
-        call_func.push(name);
-        self.take(vec![scanner::TokenKind::ParenthesOpen]);
-        for arg in self.call_args() {
-            call_func.push(arg);
+        match target.kind {
+            ExprKind::Index(collection, index) => Ok(StmtKind::SetIndex(collection, index, value)),
+            ExprKind::Field(target, field) => Ok(StmtKind::SetField(target, field, value)),
+            _ => unreachable!(),
         }
-        self.take(vec![scanner::TokenKind::ParenthesClose]);
+    }
+
+    fn call_while(&mut self) -> ParseResult<StmtKind> {
+        self.take(vec![scanner::TokenKind::While])?;
+        let cond = self.condition()?;
+        let body = self.block()?;
 
-        call_func
+        Ok(StmtKind::While(cond, body))
     }
 
-    fn call_args(&mut self) -> Vec<scanner::Token> {
+    fn call_for(&mut self) -> ParseResult<StmtKind> {
+        self.take(vec![scanner::TokenKind::For])?;
+        let name = self.ident()?;
+        self.take(vec![scanner::TokenKind::In])?;
+        let iterable = self.condition()?;
+        let body = self.block()?;
 
+        Ok(StmtKind::For(name, iterable, body))
     }
 
-    fn call_while(&mut self) {}
+    fn call_if(&mut self) -> ParseResult<StmtKind> {
+        self.take(vec![scanner::TokenKind::If])?;
+        let cond = self.condition()?;
+        let then = self.block()?;
 
-    fn relation(&mut self) {}
+        if !self.is_match(scanner::TokenKind::Else) {
+            return Ok(StmtKind::If(cond, then, None));
+        }
+        self.take(vec![scanner::TokenKind::Else])?;
 
-    fn expr(&mut self) {}
+        let otherwise = if self.is_match(scanner::TokenKind::If) {
+            let start = self.scanner.peek().span;
+            let else_if = self.call_if()?;
+            vec![Stmt { kind: else_if, span: start.to(self.prev_span) }]
+        } else {
+            self.block()?
+        };
 
-    fn term(&mut self) {
-        let mut term = self.factor();
+        Ok(StmtKind::If(cond, then, Some(otherwise)))
+    }
 
+    fn call_return(&mut self) -> ParseResult<StmtKind> {
+        self.take(vec![scanner::TokenKind::Return])?;
 
-        while self.is_match(scanner::TokenKind::OpMul) {
-            let token = self.take(vec![scanner::TokenKind::OpMul]);
+        let value = if self.is_match(scanner::TokenKind::Semicolon) || self.is_match(scanner::TokenKind::End) {
+            None
+        } else {
+            Some(self.expr()?)
+        };
+        self.semicolon()?;
 
-            match token.value.chars().nth(0).unwrap() {
-                '*'=> {
-                    term = [scanner::Token { kind: scanner::TokenKind::Mul, value: '*'.to_string()}, term, self.factor()];
-                }
+        Ok(StmtKind::Return(value))
+    }
+
+    /// Calls, indexing and field access of `target`, as in `f(1)[0].x`. Each
+    /// is a link of the chain `target` starts.
+    fn postfix(&mut self, mut target: Expr) -> ParseResult<Expr> {
+        let suffixes = [scanner::TokenKind::ParenthesOpen, scanner::TokenKind::BracketOpen, scanner::TokenKind::Dot];
+        while suffixes.iter().any(|suffix| self.is_match(suffix.clone())) {
+            self.link()?;
+            let start = target.span;
+            let kind = if self.is_match(scanner::TokenKind::ParenthesOpen) {
+                let open = self.take(vec![scanner::TokenKind::ParenthesOpen])?;
+                let args = self.expr_list(scanner::TokenKind::ParenthesClose)?;
+                self.close(scanner::TokenKind::ParenthesClose, &open)?;
+                ExprKind::Call(Box::new(target), args)
+            } else if self.is_match(scanner::TokenKind::BracketOpen) {
+                let open = self.take(vec![scanner::TokenKind::BracketOpen])?;
+                let index = self.expr()?;
+                self.close(scanner::TokenKind::BracketClose, &open)?;
+                ExprKind::Index(Box::new(target), Box::new(index))
+            } else {
+                self.take(vec![scanner::TokenKind::Dot])?;
+                ExprKind::Field(Box::new(target), self.ident()?)
+            };
+            target = Expr { kind, span: start.to(self.prev_span) };
+        }
+
+        Ok(target)
+    }
+
+    /// Comma separated expressions up to `close`, which is not taken.
+    fn expr_list(&mut self, close: scanner::TokenKind) -> ParseResult<Vec<Expr>> {
+        let mut exprs = Vec::new();
+
+        while !self.is_match(close.clone()) {
+            exprs.push(self.expr()?);
+            if !self.is_match(scanner::TokenKind::Comma) {
+                break;
             }
+            self.take(vec![scanner::TokenKind::Comma])?;
+        }
+
+        Ok(exprs)
+    }
+
+    fn expr(&mut self) -> ParseResult<Expr> {
+        let in_condition = std::mem::replace(&mut self.in_condition, false);
+        let expr = self.nested(Self::range);
+        self.in_condition = in_condition;
+        expr
+    }
+
+    fn condition(&mut self) -> ParseResult<Expr> {
+        let in_condition = std::mem::replace(&mut self.in_condition, true);
+        let expr = self.nested(Self::range);
+        self.in_condition = in_condition;
+        expr
+    }
+
+    /// `start..end` binds looser than any binary operator and does not chain.
+    fn range(&mut self) -> ParseResult<Expr> {
+        let start = self.binary(0)?;
+        if !self.is_match(scanner::TokenKind::DotDot) {
+            return Ok(start);
         }
+
+        self.take(vec![scanner::TokenKind::DotDot])?;
+        let end = self.binary(0)?;
+        Ok(Expr { span: start.span.to(end.span), kind: ExprKind::Range(Box::new(start), Box::new(end)) })
+    }
+
+    /// The binary operator at the current token and its precedence.
+    fn binary_op(&self) -> Option<(BinOp, u8)> {
+        let token = self.scanner.peek();
+        let op = match (token.kind, token.value.as_str()) {
+            (scanner::TokenKind::OpRel, "==") => (BinOp::Eq, 0),
+            (scanner::TokenKind::OpRel, "!=") => (BinOp::Ne, 0),
+            (scanner::TokenKind::OpRel, "<") => (BinOp::Lt, 0),
+            (scanner::TokenKind::OpRel, "<=") => (BinOp::Le, 0),
+            (scanner::TokenKind::OpRel, ">") => (BinOp::Gt, 0),
+            (scanner::TokenKind::OpRel, ">=") => (BinOp::Ge, 0),
+            (scanner::TokenKind::OpAdd, "+") => (BinOp::Add, 1),
+            (scanner::TokenKind::OpAdd, _) => (BinOp::Sub, 1),
+            (scanner::TokenKind::OpMul, "*") => (BinOp::Mul, 2),
+            (scanner::TokenKind::OpMul, "/") => (BinOp::Div, 2),
+            (scanner::TokenKind::OpMul, _) => (BinOp::Mod, 2),
+            _ => return None,
+        };
+
+        Some(op)
+    }
+
+    /// Precedence climbing over operators binding at least as tight as
+    /// `min_prec`. All binary operators are left associative.
+    fn binary(&mut self, min_prec: u8) -> ParseResult<Expr> {
+        self.chain(|parser| {
+            let mut lhs = parser.unary()?;
+
+            while let Some((op, prec)) = parser.binary_op() {
+                if prec < min_prec {
+                    break;
+                }
+
+                parser.link()?;
+                parser.scanner.next();
+                let rhs = parser.nested(|parser| parser.binary(prec + 1))?;
+                lhs = Expr { span: lhs.span.to(rhs.span), kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)) };
+            }
+
+            Ok(lhs)
+        })
+    }
+
+    fn unary(&mut self) -> ParseResult<Expr> {
+        let token = self.scanner.peek();
+        let op = match (&token.kind, token.value.as_str()) {
+            (scanner::TokenKind::OpAdd, "-") => UnaryOp::Neg,
+            (scanner::TokenKind::Not, _) => UnaryOp::Not,
+            _ => return self.factor(),
+        };
+
+        self.take(vec![token.kind])?;
+        let operand = self.nested(Self::unary)?;
+        Ok(Expr { span: token.span.to(operand.span), kind: ExprKind::Unary(op, Box::new(operand)) })
     }
 
-    fn factor(&mut self) -> Factor {
+    fn factor(&mut self) -> ParseResult<Expr> {
+        self.chain(|parser| {
+            let factor = parser.primary()?;
+            parser.postfix(factor)
+        })
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
         if self.is_match(scanner::TokenKind::ParenthesOpen) {
-            self.take(vec![scanner::TokenKind::ParenthesOpen]);
-            let factor = self.expr();
-            self.take(vec![scanner::TokenKind::ParenthesClose]);
+            let open = self.take(vec![scanner::TokenKind::ParenthesOpen])?;
+            let expr = self.expr()?;
+            self.close(scanner::TokenKind::ParenthesClose, &open)?;
+            Ok(expr)
+        } else if self.is_match(scanner::TokenKind::FuncDef) {
+            self.lambda()
+        } else if self.is_match(scanner::TokenKind::BracketOpen) {
+            self.list()
+        } else if self.is_match(scanner::TokenKind::Begin) {
+            self.map()
+        } else if self.is_struct_literal() {
+            self.struct_literal()
+        } else if self.is_match(scanner::TokenKind::Match) {
+            self.match_expr()
+        } else {
+            self.literal()
+        }
+    }
+
+    fn list(&mut self) -> ParseResult<Expr> {
+        let open = self.take(vec![scanner::TokenKind::BracketOpen])?;
+        let items = self.expr_list(scanner::TokenKind::BracketClose)?;
+        self.close(scanner::TokenKind::BracketClose, &open)?;
 
-            return factor
+        Ok(Expr { kind: ExprKind::List(items), span: open.span.to(self.prev_span) })
+    }
+
+    /// `Name { field: ...` or `Name {}`.
+    fn is_struct_literal(&mut self) -> bool {
+        if !self.is_match(scanner::TokenKind::Ident) || self.scanner.peek_nth(1).kind != scanner::TokenKind::Begin {
+            return false;
+        }
+
+        match self.scanner.peek_nth(2).kind {
+            scanner::TokenKind::End => !self.in_condition,
+            scanner::TokenKind::Ident => self.scanner.peek_nth(3).kind == scanner::TokenKind::Colon,
+            _ => false,
+        }
+    }
+
+    fn struct_literal(&mut self) -> ParseResult<Expr> {
+        let name = self.ident()?;
+        let open = self.take(vec![scanner::TokenKind::Begin])?;
+        let mut fields = Vec::new();
+
+        while !self.is_match(scanner::TokenKind::End) {
+            let field = self.ident()?;
+            self.take(vec![scanner::TokenKind::Colon])?;
+            fields.push((field, self.expr()?));
+            if !self.is_match(scanner::TokenKind::Comma) {
+                break;
+            }
+            self.take(vec![scanner::TokenKind::Comma])?;
         }
+        self.close(scanner::TokenKind::End, &open)?;
 
-        self.literal()
+        Ok(Expr { span: name.span.to(self.prev_span), kind: ExprKind::Struct(name, fields) })
     }
 
-    fn literal(&mut self) -> Literal {
-        let literalToken = self.take(vec![scanner::TokenKind::Int, scanner::TokenKind::String, scanner::TokenKind::Bool, scanner::TokenKind::Ident]);
-        if literalToken.kind == scanner::TokenKind::Ident && self.is_match(scanner::TokenKind::ParenthesOpen) {
-            return self.call_func(literalToken)
+    fn match_expr(&mut self) -> ParseResult<Expr> {
+        let keyword = self.take(vec![scanner::TokenKind::Match])?;
+        let value = self.condition()?;
+        let open = self.take(vec![scanner::TokenKind::Begin])?;
+        let mut arms = Vec::new();
+
+        while !self.is_match(scanner::TokenKind::End) {
+            let pattern = self.pattern()?;
+            self.take(vec![scanner::TokenKind::FatArrow])?;
+            let body = self.expr()?;
+            arms.push(Arm { span: pattern.span.to(body.span), pattern, body });
+
+            if !self.is_match(scanner::TokenKind::Comma) {
+                break;
+            }
+            self.take(vec![scanner::TokenKind::Comma])?;
         }
+        self.close(scanner::TokenKind::End, &open)?;
 
-        vec![literalToken]
+        Ok(Expr { kind: ExprKind::Match(Box::new(value), arms), span: keyword.span.to(self.prev_span) })
+    }
+
+    fn pattern(&mut self) -> ParseResult<Pattern> {
+        self.nested(|parser| {
+            let token = parser.scanner.peek();
+            let kind = match (&token.kind, token.value.as_str()) {
+                (scanner::TokenKind::Ident, "_") => {
+                    parser.take(vec![scanner::TokenKind::Ident])?;
+                    PatternKind::Wildcard
+                }
+                (scanner::TokenKind::Ident, _) => {
+                    let name = parser.ident()?;
+                    if !parser.is_match(scanner::TokenKind::ParenthesOpen) {
+                        return Ok(Pattern { span: name.span, kind: PatternKind::Binding(name) });
+                    }
+
+                    let open = parser.take(vec![scanner::TokenKind::ParenthesOpen])?;
+                    let mut fields = Vec::new();
+                    while !parser.is_match(scanner::TokenKind::ParenthesClose) {
+                        fields.push(parser.pattern()?);
+                        if !parser.is_match(scanner::TokenKind::Comma) {
+                            break;
+                        }
+                        parser.take(vec![scanner::TokenKind::Comma])?;
+                    }
+                    parser.close(scanner::TokenKind::ParenthesClose, &open)?;
+                    PatternKind::Variant(name, fields)
+                }
+                (scanner::TokenKind::OpAdd, "-") => {
+                    parser.take(vec![scanner::TokenKind::OpAdd])?;
+                    match parser.pattern_literal()? {
+                        Literal::Int(value) => PatternKind::Literal(Literal::Int(-value)),
+                        Literal::Float(value) => PatternKind::Literal(Literal::Float(-value)),
+                        _ => return Err(ParseError::new("expected a number after `-`".to_string(), parser.prev_span)),
+                    }
+                }
+                _ => PatternKind::Literal(parser.pattern_literal()?),
+            };
+
+            Ok(Pattern { kind, span: token.span.to(parser.prev_span) })
+        })
+    }
+
+    fn pattern_literal(&mut self) -> ParseResult<Literal> {
+        let literal_kinds = [scanner::TokenKind::Int, scanner::TokenKind::Float, scanner::TokenKind::String, scanner::TokenKind::Bool];
+        if !literal_kinds.contains(&self.scanner.peek().kind) {
+            return Err(self.unexpected("a pattern"));
+        }
+
+        match self.literal()?.kind {
+            ExprKind::Literal(literal) => Ok(literal),
+            _ => unreachable!(),
+        }
+    }
+
+    fn map(&mut self) -> ParseResult<Expr> {
+        let open = self.take(vec![scanner::TokenKind::Begin])?;
+        let mut entries = Vec::new();
+
+        while !self.is_match(scanner::TokenKind::End) {
+            let key = self.expr()?;
+            self.take(vec![scanner::TokenKind::Colon])?;
+            entries.push((key, self.expr()?));
+            if !self.is_match(scanner::TokenKind::Comma) {
+                break;
+            }
+            self.take(vec![scanner::TokenKind::Comma])?;
+        }
+        self.close(scanner::TokenKind::End, &open)?;
+
+        Ok(Expr { kind: ExprKind::Map(entries), span: open.span.to(self.prev_span) })
+    }
+
+    fn lambda(&mut self) -> ParseResult<Expr> {
+        let func_def = self.take(vec![scanner::TokenKind::FuncDef])?;
+        let open = self.take(vec![scanner::TokenKind::ParenthesOpen])?;
+        let args = self.func_args()?;
+        self.close(scanner::TokenKind::ParenthesClose, &open)?;
+        let body = self.block()?;
+
+        Ok(Expr { kind: ExprKind::Lambda(args, body), span: func_def.span.to(self.prev_span) })
+    }
+
+    fn literal(&mut self) -> ParseResult<Expr> {
+        let literal_kinds = [scanner::TokenKind::Int, scanner::TokenKind::Float, scanner::TokenKind::String, scanner::TokenKind::Bool, scanner::TokenKind::Ident];
+        if !literal_kinds.contains(&self.scanner.peek().kind) {
+            return Err(self.unexpected("an expression"));
+        }
+
+        let literal_token = self.take(literal_kinds.to_vec())?;
+        let span = literal_token.span;
+
+        // The scanner hands out validated, normalised numbers.
+        let literal = match literal_token.kind {
+            scanner::TokenKind::Ident => return Ok(Expr { kind: ExprKind::Ident(Ident { name: literal_token.value, span }), span }),
+            scanner::TokenKind::Int => Literal::Int(literal_token.value.parse().unwrap()),
+            scanner::TokenKind::Float => Literal::Float(literal_token.value.parse().unwrap()),
+            scanner::TokenKind::Bool => Literal::Bool(literal_token.value == "true"),
+            _ => Literal::String(literal_token.value[1..literal_token.value.len() - 1].to_string()),
+        };
+
+        Ok(Expr { kind: ExprKind::Literal(literal), span })
     }
 }
 
@@ -176,6 +1008,10 @@ impl Parser {
 mod tests {
 use super::*;
 
+    fn span(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span { start, end, line, column }
+    }
+
     #[test]
     fn parse_hello_world() {
         let text = r#"
@@ -186,23 +1022,507 @@ use super::*;
 
         let scanner = scanner::Scanner::new(text);
         let mut parser = Parser::new(scanner);
-        let actual = parser.parse();
+        let actual = parser.parse().unwrap();
 
         assert_eq!(actual, vec![
-            (
-                scanner::Token {kind: scanner::TokenKind::FuncDef, value: "fn".to_string()},
-                vec![scanner::Token {kind: scanner::TokenKind::Ident, value: "main".to_string()}],
-                vec![],
-                vec![
-                     vec![
-                        (
-                            scanner::Token {kind: scanner::TokenKind::CallFunc, value: "".to_string()},
-                            vec![scanner::Token {kind: scanner::TokenKind::Ident, value: "print".to_string()}],
-                            vec![scanner::Token {kind: scanner::TokenKind::String, value: "\"Hello World\"".to_string()}],
-                        )
-                     ]
-                ]
-            )
+            Item::FuncDef(FuncDef {
+                name: Ident { name: "main".to_string(), span: span(16, 20, 2, 16) },
+                args: vec![],
+                body: vec![
+                    Stmt {
+                        kind: StmtKind::Expr(Expr {
+                            kind: ExprKind::Call(
+                                Box::new(Expr { kind: ExprKind::Ident(Ident { name: "print".to_string(), span: span(41, 46, 3, 17) }), span: span(41, 46, 3, 17) }),
+                                vec![
+                                    Expr { kind: ExprKind::Literal(Literal::String("Hello World".to_string())), span: span(47, 60, 3, 23) },
+                                ],
+                            ),
+                            span: span(41, 61, 3, 17),
+                        }),
+                        span: span(41, 62, 3, 17),
+                    },
+                ],
+                doc: None,
+                span: span(13, 76, 2, 13),
+            }),
+        ]);
+    }
+
+    #[test]
+    fn parse_literals_and_doc_comments() {
+        let text = r#"
+            /// Says hi.
+            fn greet(name, times) {
+                log(name, 0x10, 2.5, false, "a\"b", 6 * 7 % 4);
+            }
+        "#;
+
+        let actual = Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+        let Item::FuncDef(func_def) = &actual[0] else { panic!() };
+
+        assert_eq!(func_def.doc, Some("Says hi.".to_string()));
+        assert_eq!(func_def.args.iter().map(|arg| arg.name.as_str()).collect::<Vec<_>>(), vec!["name", "times"]);
+
+        let StmtKind::Expr(Expr { kind: ExprKind::Call(_, args), .. }) = &func_def.body[0].kind else {
+            panic!("expected a call, got {:?}", func_def.body[0]);
+        };
+        let kinds: Vec<&ExprKind> = args.iter().map(|arg| &arg.kind).collect();
+        assert_eq!(kinds[..5], [
+            &ExprKind::Ident(Ident { name: "name".to_string(), span: args[0].span }),
+            &ExprKind::Literal(Literal::Int(16)),
+            &ExprKind::Literal(Literal::Float(2.5)),
+            &ExprKind::Literal(Literal::Bool(false)),
+            &ExprKind::Literal(Literal::String("a\"b".to_string())),
+        ]);
+
+        let ExprKind::Binary(BinOp::Mod, lhs, _) = &args[5].kind else {
+            panic!("expected `%` at the top, got {:?}", args[5]);
+        };
+        assert!(matches!(lhs.kind, ExprKind::Binary(BinOp::Mul, _, _)));
+        assert_eq!(&text[args[5].span.start..args[5].span.end], "6 * 7 % 4");
+    }
+
+    fn sexp(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Literal(Literal::Int(value)) => value.to_string(),
+            ExprKind::Literal(literal) => format!("{:?}", literal),
+            ExprKind::Ident(name) => name.name.clone(),
+            ExprKind::Call(callee, args) => {
+                let args: Vec<String> = args.iter().map(sexp).collect();
+                format!("({} {})", sexp(callee), args.join(" "))
+            }
+            ExprKind::Index(target, index) => format!("(index {} {})", sexp(target), sexp(index)),
+            ExprKind::List(items) => {
+                let items: Vec<String> = items.iter().map(sexp).collect();
+                format!("(list {})", items.join(" "))
+            }
+            ExprKind::Map(entries) => {
+                let entries: Vec<String> = entries.iter().map(|(key, value)| format!("({} {})", sexp(key), sexp(value))).collect();
+                format!("(map {})", entries.join(" "))
+            }
+            ExprKind::Field(target, field) => format!("(. {} {})", sexp(target), field.name),
+            ExprKind::Struct(name, fields) => {
+                let fields: Vec<String> = fields.iter().map(|(field, value)| format!("({} {})", field.name, sexp(value))).collect();
+                format!("(struct {} {})", name.name, fields.join(" "))
+            }
+            ExprKind::Match(value, arms) => {
+                let arms: Vec<String> = arms.iter().map(|arm| format!("({} {})", sexp_pattern(&arm.pattern), sexp(&arm.body))).collect();
+                format!("(match {} {})", sexp(value), arms.join(" "))
+            }
+            ExprKind::Lambda(args, body) => {
+                let args: Vec<&str> = args.iter().map(|arg| arg.name.as_str()).collect();
+                format!("(fn ({}) {})", args.join(" "), sexp_block(body))
+            }
+            ExprKind::Unary(op, operand) => format!("({:?} {})", op, sexp(operand)),
+            ExprKind::Binary(op, lhs, rhs) => format!("({:?} {} {})", op, sexp(lhs), sexp(rhs)),
+            ExprKind::Range(start, end) => format!("(.. {} {})", sexp(start), sexp(end)),
+        }
+    }
+
+    fn sexp_pattern(pattern: &Pattern) -> String {
+        match &pattern.kind {
+            PatternKind::Literal(Literal::Int(value)) => value.to_string(),
+            PatternKind::Literal(literal) => format!("{:?}", literal),
+            PatternKind::Wildcard => "_".to_string(),
+            PatternKind::Binding(name) => name.name.clone(),
+            PatternKind::Variant(name, fields) => {
+                let fields: Vec<String> = fields.iter().map(sexp_pattern).collect();
+                format!("({} {})", name.name, fields.join(" "))
+            }
+        }
+    }
+
+    fn parse_expr(text: &str) -> Expr {
+        let mut parser = Parser::new(scanner::Scanner::new(text));
+        let expr = parser.expr().unwrap();
+        assert!(parser.is_match(scanner::TokenKind::Eof), "{:?} was not fully parsed", text);
+        expr
+    }
+
+    #[test]
+    fn parse_expressions() {
+        let cases = [
+            ("i % 15 == 0", "(Eq (Mod i 15) 0)"),
+            ("i < end", "(Lt i end)"),
+            ("i + 1", "(Add i 1)"),
+            ("1 + 2 * 3 - 4", "(Sub (Add 1 (Mul 2 3)) 4)"),
+            ("8 / 4 / 2", "(Div (Div 8 4) 2)"),
+            ("10 - 3 - 2", "(Sub (Sub 10 3) 2)"),
+            ("(1 + 2) * 3", "(Mul (Add 1 2) 3)"),
+            ("-x * -(y + 1)", "(Mul (Neg x) (Neg (Add y 1)))"),
+            ("!done == false", "(Eq (Not done) Bool(false))"),
+            ("- -1", "(Neg (Neg 1))"),
+            ("a + b >= c % d != e", "(Ne (Ge (Add a b) (Mod c d)) e)"),
+            ("f(i % 3, g(-1)) <= 2", "(Le (f (Mod i 3) (g (Neg 1))) 2)"),
+            ("f(1)(2)()", "(((f 1) 2) )"),
+            ("fn(x, y) { return x + y; }(1, 2) * 3", "(Mul ((fn (x y) [(return (Add x y))]) 1 2) 3)"),
+            ("(fn() { f = fn() {}; })()", "((fn () [(= f (fn () []))]) )"),
+            ("[1, [2], {}][0]", "(index (list 1 (list 2) (map )) 0)"),
+            ("{\"a\": 1, k: [x + 1]}[\"a\"] * m[i][-j]", "(Mul (index (map (String(\"a\") 1) (k (list (Add x 1)))) String(\"a\")) (index (index m i) (Neg j)))"),
+            ("f(x)[0](y)", "((index (f x) 0) y)"),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(sexp(&parse_expr(text)), expected, "{}", text);
+        }
+
+        let expr = parse_expr("-(a) * b + 1");
+        assert_eq!(expr.span, span(0, 12, 1, 1));
+        let ExprKind::Binary(BinOp::Add, lhs, _) = expr.kind else { panic!() };
+        assert_eq!(lhs.span, span(0, 8, 1, 1));
+    }
+
+    fn sexp_block(block: &Block) -> String {
+        let stmts: Vec<String> = block.iter().map(|stmt| match &stmt.kind {
+            StmtKind::Expr(expr) => sexp(expr),
+            StmtKind::Assign(name, value) => format!("(= {} {})", name.name, sexp(value)),
+            StmtKind::SetIndex(target, index, value) => format!("(= (index {} {}) {})", sexp(target), sexp(index), sexp(value)),
+            StmtKind::SetField(target, field, value) => format!("(= (. {} {}) {})", sexp(target), field.name, sexp(value)),
+            StmtKind::While(cond, body) => format!("(while {} {})", sexp(cond), sexp_block(body)),
+            StmtKind::For(name, iterable, body) => format!("(for {} {} {})", name.name, sexp(iterable), sexp_block(body)),
+            StmtKind::If(cond, then, None) => format!("(if {} {})", sexp(cond), sexp_block(then)),
+            StmtKind::If(cond, then, Some(otherwise)) => {
+                format!("(if {} {} {})", sexp(cond), sexp_block(then), sexp_block(otherwise))
+            }
+            StmtKind::Break => "break".to_string(),
+            StmtKind::Continue => "continue".to_string(),
+            StmtKind::Return(None) => "return".to_string(),
+            StmtKind::Return(Some(value)) => format!("(return {})", sexp(value)),
+        }).collect();
+
+        format!("[{}]", stmts.join(" "))
+    }
+
+    #[test]
+    fn parse_fizz_buzz() {
+        let text = r#"
+            fn main() {
+                fizzbuzz(1, 100)
+            }
+
+            fn fizzbuzz(start, end) {
+                i = start;
+                while(true){
+                    if (i < end) {
+                    break;
+                    }
+                    if(i % 15 == 0){
+                        print("FizzBuzz");
+                    }else if(i % 3 == 0){
+                        print("Fizz");
+                    }else if(i % 5 == 0){
+                        print("Buzz");
+                    }else {
+                        print(i);
+                    }
+                    i = i + 1;
+                }
+            }
+        "#;
+
+        let actual = Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+        let funcs: Vec<(String, String)> = actual.iter().map(|item| {
+            let Item::FuncDef(func_def) = item else { panic!() };
+            (func_def.name.name.clone(), sexp_block(&func_def.body))
+        }).collect();
+
+        assert_eq!(funcs, vec![
+            ("main".to_string(), "[(fizzbuzz 1 100)]".to_string()),
+            ("fizzbuzz".to_string(), concat!(
+                "[(= i start) (while Bool(true) [",
+                "(if (Lt i end) [break]) ",
+                "(if (Eq (Mod i 15) 0) [(print String(\"FizzBuzz\"))] ",
+                "[(if (Eq (Mod i 3) 0) [(print String(\"Fizz\"))] ",
+                "[(if (Eq (Mod i 5) 0) [(print String(\"Buzz\"))] [(print i)])])]) ",
+                "(= i (Add i 1))",
+                "])]",
+            ).to_string()),
+        ]);
+
+        let Item::FuncDef(fizzbuzz) = &actual[1] else { panic!() };
+        let StmtKind::While(_, body) = &fizzbuzz.body[1].kind else { panic!() };
+        let StmtKind::If(_, _, Some(otherwise)) = &body[1].kind else { panic!() };
+        assert_eq!(&text[otherwise[0].span.start..otherwise[0].span.start + 16], "if(i % 3 == 0){\n");
+        assert_eq!(&text[body[2].span.start..body[2].span.end], "i = i + 1;");
+    }
+
+    #[test]
+    fn parse_return_and_expression_statements() {
+        let text = "fn f(x) { if x { return; } -x; return x * 2 }";
+        let actual = Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+        let Item::FuncDef(func_def) = &actual[0] else { panic!() };
+
+        assert_eq!(sexp_block(&func_def.body), "[(if x [return]) (Neg x) (return (Mul x 2))]");
+    }
+
+    #[test]
+    fn parse_index_assignment() {
+        let text = "fn f(a) { a[0] = {\"k\": a[1]}; a[i][j] = []; a = [1, 2]; }";
+        let actual = Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+        let Item::FuncDef(func_def) = &actual[0] else { panic!() };
+
+        assert_eq!(sexp_block(&func_def.body), concat!(
+            "[(= (index a 0) (map (String(\"k\") (index a 1)))) ",
+            "(= (index (index a i) j) (list )) ",
+            "(= a (list 1 2))]",
+        ));
+
+        let errors = Parser::new(scanner::Scanner::new("fn f() {\n    g(x) = 1;\n    [1, 2;\n}")).parse().unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Syntax Error at line 2, column 5: cannot assign to this expression",
+            "Syntax Error at line 3, column 10: expected `]`, found `;`",
         ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parse_globals() {
+        let text = "/// The answer.\nconst ANSWER = 6 * 7;\nlet count = 0;\nfn main() { count = count + ANSWER; }";
+        let actual = Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+
+        let Item::Global(answer) = &actual[0] else { panic!() };
+        assert_eq!((answer.name.name.as_str(), answer.mutable, answer.doc.as_deref()), ("ANSWER", false, Some("The answer.")));
+        assert_eq!(sexp(&answer.value), "(Mul 6 7)");
+        assert_eq!(&text[answer.span.start..answer.span.end], "const ANSWER = 6 * 7;");
+
+        let Item::Global(count) = &actual[1] else { panic!() };
+        assert!(count.mutable);
+        assert!(matches!(actual[2], Item::FuncDef(_)));
+
+        let errors = Parser::new(scanner::Scanner::new("const = 1;\nlet x 2;\nfn main() {}")).parse().unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Syntax Error at line 1, column 7: expected identifier, found `=`",
+            "Syntax Error at line 2, column 7: expected `=`, found `2`",
+        ]);
+    }
+
+    #[test]
+    fn parse_structs() {
+        let text = "/// A point.\nstruct Point { x, y }\nstruct Unit {}\nimpl Point {\n    fn norm(self) { return self.x * self.x + self.y * self.y; }\n}\nfn main() {\n    p = Point { x: 1, y: f(2).z };\n    p.x = Unit {};\n    if p.x == (Unit {}) {}\n}";
+        let actual = Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+
+        let Item::Struct(point) = &actual[0] else { panic!() };
+        let fields: Vec<&str> = point.fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!((point.name.name.as_str(), fields, point.doc.as_deref()), ("Point", vec!["x", "y"], Some("A point.")));
+        let Item::Struct(unit) = &actual[1] else { panic!() };
+        assert!(unit.fields.is_empty());
+
+        let Item::Impl(methods) = &actual[2] else { panic!() };
+        assert_eq!(methods.name.name, "Point");
+        assert_eq!(sexp_block(&methods.methods[0].body), "[(return (Add (Mul (. self x) (. self x)) (Mul (. self y) (. self y))))]");
+        assert_eq!(&text[methods.span.start..methods.span.end], "impl Point {\n    fn norm(self) { return self.x * self.x + self.y * self.y; }\n}");
+
+        let Item::FuncDef(main) = &actual[3] else { panic!() };
+        assert_eq!(sexp_block(&main.body), "[(= p (struct Point (x 1) (y (. (f 2) z)))) (= (. p x) (struct Unit )) (if (Eq (. p x) (struct Unit )) [])]");
+
+        // In a condition `Unit {}` is `Unit` and the body.
+        let actual = Parser::new(scanner::Scanner::new("fn main() { if p == Unit {} {} }")).parse().unwrap();
+        let Item::FuncDef(main) = &actual[0] else { panic!() };
+        assert_eq!(sexp_block(&main.body), "[(if (Eq p Unit) []) (map )]");
+
+        let errors = Parser::new(scanner::Scanner::new("impl P {\n    fn f(x) {}\n}\nstruct Q { 1 }")).parse().unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Syntax Error at line 2, column 8: method `f` must take `self` as its first argument",
+            "Syntax Error at line 4, column 12: expected identifier, found `1`",
+        ]);
+    }
+
+    #[test]
+    fn parse_enums_and_match() {
+        let text = "/// Shapes.\nenum Shape { Circle(r), Rect(w, h), Empty }\nenum Never {}\nfn main() {\n    match s { Circle(r) => r, Rect(1, -2.5) => 0, Rect(w, Empty) => w, Empty => \"e\", x => x, _ => match x {}, };\n}";
+        let actual = Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+
+        let Item::Enum(shape) = &actual[0] else { panic!() };
+        let variants: Vec<(&str, usize)> = shape.variants.iter().map(|variant| (variant.name.name.as_str(), variant.fields.len())).collect();
+        assert_eq!((shape.name.name.as_str(), variants, shape.doc.as_deref()), ("Shape", vec![("Circle", 1), ("Rect", 2), ("Empty", 0)], Some("Shapes.")));
+        assert_eq!(&text[shape.variants[1].span.start..shape.variants[1].span.end], "Rect(w, h)");
+        let Item::Enum(never) = &actual[1] else { panic!() };
+        assert!(never.variants.is_empty());
+
+        let Item::FuncDef(main) = &actual[2] else { panic!() };
+        assert_eq!(
+            sexp_block(&main.body),
+            "[(match s ((Circle r) r) ((Rect 1 Float(-2.5)) 0) ((Rect w Empty) w) (Empty String(\"e\")) (x x) (_ (match x )))]",
+        );
+
+        let errors = [
+            ("1 + 1 => 2", "Syntax Error at line 1, column 25: expected `=>`, found `+`"),
+            ("- \"a\" => 1", "Syntax Error at line 1, column 25: expected a number after `-`"),
+            ("y 1", "Syntax Error at line 1, column 25: expected `=>`, found `1`"),
+            ("(1) => 1", "Syntax Error at line 1, column 23: expected a pattern, found `(`"),
+        ];
+        for (arm, message) in errors {
+            let errors = Parser::new(scanner::Scanner::new(&format!("fn main() {{ match x {{ {} }}; }}", arm))).parse().unwrap_err();
+            assert_eq!(errors[0].to_string(), message, "{}", arm);
+        }
+    }
+
+    #[test]
+    fn parse_for_loops() {
+        let text = "fn main() {\n    for i in 0..n + 1 { if i == 2 { continue; } print(i); }\n    for x in Items {} {};\n    r = a < b..-c;\n}";
+        let actual = Parser::new(scanner::Scanner::new(text)).parse().unwrap();
+
+        let Item::FuncDef(main) = &actual[0] else { panic!() };
+        assert_eq!(
+            sexp_block(&main.body),
+            "[(for i (.. 0 (Add n 1)) [(if (Eq i 2) [continue]) (print i)]) (for x Items []) (map ) (= r (.. (Lt a b) (Neg c)))]",
+        );
+        let StmtKind::For(_, range, _) = &main.body[0].kind else { panic!() };
+        assert_eq!(&text[range.span.start..range.span.end], "0..n + 1");
+
+        let errors = [
+            ("for 1 in x {}", "Syntax Error at line 1, column 17: expected identifier, found `1`"),
+            ("for i x {}", "Syntax Error at line 1, column 19: expected `in`, found `x`"),
+            ("for i in 0..1..2 {}", "Syntax Error at line 1, column 26: expected `{`, found `..`"),
+            ("continue", "Syntax Error at line 1, column 22: expected `;`, found `x`"),
+        ];
+        for (stmt, message) in errors {
+            let errors = Parser::new(scanner::Scanner::new(&format!("fn main() {{ {} x }}", stmt))).parse().unwrap_err();
+            assert_eq!(errors[0].to_string(), message, "{}", stmt);
+        }
+    }
+
+    #[test]
+    fn parse_error_reports_location() {
+        let text = r#"
+            fn main() {
+                print("Hello World")
+                print("again");
+            }
+        "#;
+
+        let scanner = scanner::Scanner::new(text);
+        let mut parser = Parser::new(scanner);
+        let errors = parser.parse().unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "Syntax Error at line 4, column 17: expected `;`, found `print`");
+    }
+
+    #[test]
+    fn parse_reports_every_error() {
+        let text = r#"
+            fn main() {
+                x = ;
+                print("a" "b");
+                if x { y = 1 + ; } else { z = 2; }
+                ok(1);
+                w = 1 @ 2;
+            }
+
+            stray = 1;
+
+            fn broken( {
+                never(parsed);
+            }
+
+            fn fine(a, b) {
+                return a + b
+            }
+
+            fn unclosed() {
+                print(
+        "#;
+
+        let errors = Parser::new(scanner::Scanner::new(text)).parse().unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+
+        assert_eq!(messages, vec![
+            "Syntax Error at line 3, column 21: expected an expression, found `;`",
+            "Syntax Error at line 4, column 27: expected `)`, found string literal",
+            "Syntax Error at line 5, column 32: expected an expression, found `;`",
+            "Syntax Error at line 7, column 23: unexpected character `@`",
+            "Syntax Error at line 10, column 13: expected an item, found `stray`",
+            "Syntax Error at line 12, column 24: expected identifier, found `{`",
+            "Syntax Error at line 22, column 9: expected an expression, found end of input",
+        ]);
+    }
+
+    /// Parses `text` and runs every later pass over the program, on a thread
+    /// with the stack the default limits are meant for.
+    fn parse_and_run(text: String) -> Result<(), Vec<ParseError>> {
+        let run = move || {
+            let program = Parser::new(scanner::Scanner::new(&text)).parse()?;
+            crate::check::check(&program);
+            crate::printer::print(&program);
+            crate::dump::program(&program).to_json();
+            // Most of these fail at run time, but only after going all the way down.
+            let _ = crate::evaluator::Evaluator::new(Vec::new()).apply(program);
+            Ok(())
+        };
+        std::thread::Builder::new().stack_size(STACK_SIZE).spawn(run).unwrap().join().unwrap()
+    }
+
+    #[test]
+    fn parse_limits_nesting() {
+        let mut parser = Parser::new(scanner::Scanner::new("((1))")).with_nesting_limit(3);
+        assert_eq!(sexp(&parser.expr().unwrap()), "1");
+
+        let mut parser = Parser::new(scanner::Scanner::new("(((1)))")).with_nesting_limit(3);
+        assert_eq!(parser.expr().unwrap_err().to_string(), "Syntax Error at line 1, column 4: nesting is deeper than the limit of 3");
+
+        // The default limit must stop each kind of nesting before the parser
+        // or a later pass runs out of stack.
+        let deep = |open: &str, close: &str, depth: usize| {
+            let text = format!("fn main() {{\n    x = {}1{};\n}}\nfn ok() {{}}", open.repeat(depth), close.repeat(depth));
+            parse_and_run(text)
+        };
+        // Each `open` nests one level deeper, or two for `!(`.
+        let nestings = [
+            ("(", ")", 1), ("-", "", 1), ("!(", ")", 2), ("[", "]", 1), ("{\"k\": ", "}", 1), ("f(", ")", 1),
+            ("", "()", 1), ("", "[0]", 1), ("", ".x", 1), ("P { x: ", " }", 1), ("match x { _ => ", " }", 1),
+            ("", " + 1", 1), ("", " < 1", 1), ("", " * 2 - 1", 2), ("1 + (", ")", 2),
+        ];
+        for (open, close, levels) in nestings {
+            // The function body and the assignment take the first two levels.
+            assert!(deep(open, close, (DEFAULT_NESTING_LIMIT - 2) / levels).is_ok(), "{}1{}", open, close);
+            let errors = deep(open, close, 100_000).unwrap_err();
+            assert_eq!(errors[0].message, format!("nesting is deeper than the limit of {}", DEFAULT_NESTING_LIMIT));
+            // Recovery stops at the first `}`, so those nestings leave stray ones behind.
+            if !close.contains('}') {
+                assert_eq!(errors.len(), 1, "{:?}", errors);
+            }
+        }
+        let errors = deep("fn() { ", " }()", 100_000).unwrap_err();
+        assert_eq!(errors[0].message, format!("nesting is deeper than the limit of {}", DEFAULT_NESTING_LIMIT));
+
+        for open in ["if x { ", "while x { ", "for i in x { "] {
+            let body = |depth: usize| format!("fn main() {{ {}{} }}\nfn ok() {{}}", open.repeat(depth), "} ".repeat(depth));
+            assert!(parse_and_run(body(DEFAULT_NESTING_LIMIT - 1)).is_ok(), "{}", open);
+
+            // The condition of the innermost statement is one level too deep.
+            let errors = parse_and_run(body(100_000)).unwrap_err();
+            let column = 13 + open.len() * (DEFAULT_NESTING_LIMIT - 1) + open.len() - 4;
+            let message = format!("nesting is deeper than the limit of {}", DEFAULT_NESTING_LIMIT);
+            assert_eq!(errors[0].to_string(), format!("Syntax Error at line 1, column {}: {}", column, message));
+        }
+    }
+
+    #[test]
+    fn parse_long_chains() {
+        // Each link of a chain nests what came before one level deeper, but
+        // the default limit leaves room for long ones.
+        let text = format!("fn main() {{\n    s{};\n}}", ".f()".repeat(100));
+        assert_eq!(parse_and_run(text), Ok(()));
+
+        let text = format!("fn main() {{\n    a{};\n}}", ".b".repeat(100));
+        let program = Parser::new(scanner::Scanner::new(&text)).parse().unwrap();
+        assert_eq!(crate::printer::print(&program), format!("fn main() {{\n    a{};\n}}\n", ".b".repeat(100)));
+    }
+
+    #[test]
+    fn parse_recovers_the_rest_of_a_block() {
+        let text = "fn main() {\n    a = (1;\n    b = 2;\n}\nfn other() { c = 3; }";
+        let mut parser = Parser::new(scanner::Scanner::new(text));
+
+        let errors = parser.parse().unwrap_err();
+        assert_eq!(errors, vec![ParseError {
+            message: "expected `)`, found `;`".to_string(),
+            span: span(22, 23, 2, 11),
+            labels: vec![Label::new(span(20, 21, 2, 9), "unclosed `(`")],
+            help: None,
+        }]);
+    }
+}
@@ -0,0 +1,566 @@
+use super::parser::{
+    BinOp, EnumDef, Expr, ExprKind, FuncDef, Global, Impl, Item, Literal, Pattern, PatternKind, Program, Stmt, StmtKind, StructDef,
+    UnaryOp,
+};
+
+const INDENT: &str = "    ";
+
+/// Prints `program` as canonical RML source. Parsing the result gives back
+/// the same tree, apart from spans.
+pub fn print(program: &Program) -> String {
+    let mut printer = Printer { out: String::new(), depth: 0, in_condition: false };
+    for (i, item) in program.iter().enumerate() {
+        // Runs of globals stay together; functions get a blank line around them.
+        let is_global = |item: &Item| matches!(item, Item::Global(_));
+        if i > 0 && !(is_global(item) && is_global(&program[i - 1])) {
+            printer.out.push('\n');
+        }
+        printer.item(item);
+    }
+
+    printer.out
+}
+
+pub fn print_expr(expr: &Expr) -> String {
+    let mut printer = Printer { out: String::new(), depth: 0, in_condition: false };
+    printer.expr(expr);
+    printer.out
+}
+
+struct Printer {
+    out: String,
+    depth: usize,
+    /// Printing an `if` or `while` condition, where `Name {}` needs parentheses.
+    in_condition: bool,
+}
+
+impl Printer {
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::FuncDef(func_def) => self.func_def(func_def),
+            Item::Global(global) => self.global(global),
+            Item::Struct(struct_def) => self.struct_def(struct_def),
+            Item::Impl(impl_def) => self.impl_def(impl_def),
+            Item::Enum(enum_def) => self.enum_def(enum_def),
+        }
+    }
+
+    fn doc(&mut self, doc: &Option<String>) {
+        if let Some(doc) = doc {
+            for line in doc.split('\n') {
+                self.line(format!("/// {}", line).trim_end());
+            }
+        }
+    }
+
+    fn func_def(&mut self, func_def: &FuncDef) {
+        self.doc(&func_def.doc);
+        let args: Vec<&str> = func_def.args.iter().map(|arg| arg.name.as_str()).collect();
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push_str(&format!("fn {}({}) ", func_def.name.name, args.join(", ")));
+        self.block(&func_def.body);
+        self.out.push('\n');
+    }
+
+    fn global(&mut self, global: &Global) {
+        self.doc(&global.doc);
+        let keyword = if global.mutable { "let" } else { "const" };
+        self.out.push_str(&format!("{} {} = ", keyword, global.name.name));
+        self.expr(&global.value);
+        self.out.push_str(";\n");
+    }
+
+    fn struct_def(&mut self, struct_def: &StructDef) {
+        self.doc(&struct_def.doc);
+        let fields: Vec<&str> = struct_def.fields.iter().map(|field| field.name.as_str()).collect();
+        if fields.is_empty() {
+            self.line(&format!("struct {} {{}}", struct_def.name.name));
+        } else {
+            self.line(&format!("struct {} {{ {} }}", struct_def.name.name, fields.join(", ")));
+        }
+    }
+
+    fn impl_def(&mut self, impl_def: &Impl) {
+        self.line(&format!("impl {} {{", impl_def.name.name));
+        self.depth += 1;
+        for (i, method) in impl_def.methods.iter().enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+            }
+            self.func_def(method);
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    fn enum_def(&mut self, enum_def: &EnumDef) {
+        self.doc(&enum_def.doc);
+        let variants: Vec<String> = enum_def.variants.iter().map(|variant| {
+            if variant.fields.is_empty() {
+                return variant.name.name.clone();
+            }
+            let fields: Vec<&str> = variant.fields.iter().map(|field| field.name.as_str()).collect();
+            format!("{}({})", variant.name.name, fields.join(", "))
+        }).collect();
+
+        if variants.is_empty() {
+            self.line(&format!("enum {} {{}}", enum_def.name.name));
+        } else {
+            self.line(&format!("enum {} {{ {} }}", enum_def.name.name, variants.join(", ")));
+        }
+    }
+
+    /// Prints `{ ... }` starting at the current position, without a newline after `}`.
+    fn block(&mut self, block: &[Stmt]) {
+        if block.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.depth += 1;
+        let in_condition = std::mem::replace(&mut self.in_condition, false);
+        for stmt in block {
+            self.stmt(stmt);
+        }
+        self.in_condition = in_condition;
+        self.depth -= 1;
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push('}');
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.stmt_kind(&stmt.kind);
+        self.out.push('\n');
+    }
+
+    fn stmt_kind(&mut self, kind: &StmtKind) {
+        match kind {
+            StmtKind::Expr(expr) => {
+                self.expr(expr);
+                self.out.push(';');
+            }
+            StmtKind::Assign(name, value) => {
+                self.out.push_str(&format!("{} = ", name.name));
+                self.expr(value);
+                self.out.push(';');
+            }
+            StmtKind::SetIndex(target, index, value) => {
+                self.operand(target, precedence(target) < ATOM);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push_str("] = ");
+                self.expr(value);
+                self.out.push(';');
+            }
+            StmtKind::SetField(target, field, value) => {
+                self.operand(target, precedence(target) < ATOM);
+                self.out.push_str(&format!(".{} = ", field.name));
+                self.expr(value);
+                self.out.push(';');
+            }
+            StmtKind::While(cond, body) => {
+                self.out.push_str("while ");
+                self.condition(cond);
+                self.out.push(' ');
+                self.block(body);
+            }
+            StmtKind::For(name, iterable, body) => {
+                self.out.push_str(&format!("for {} in ", name.name));
+                self.condition(iterable);
+                self.out.push(' ');
+                self.block(body);
+            }
+            StmtKind::If(cond, then, otherwise) => {
+                self.out.push_str("if ");
+                self.condition(cond);
+                self.out.push(' ');
+                self.block(then);
+
+                match otherwise.as_deref() {
+                    None => {}
+                    Some([Stmt { kind: else_if @ StmtKind::If(..), .. }]) => {
+                        self.out.push_str(" else ");
+                        self.stmt_kind(else_if);
+                    }
+                    Some(otherwise) => {
+                        self.out.push_str(" else ");
+                        self.block(otherwise);
+                    }
+                }
+            }
+            StmtKind::Break => self.out.push_str("break;"),
+            StmtKind::Continue => self.out.push_str("continue;"),
+            StmtKind::Return(None) => self.out.push_str("return;"),
+            StmtKind::Return(Some(value)) => {
+                self.out.push_str("return ");
+                self.expr(value);
+                self.out.push(';');
+            }
+        }
+    }
+
+    fn condition(&mut self, cond: &Expr) {
+        self.in_condition = true;
+        self.expr(cond);
+        self.in_condition = false;
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Literal(literal) => self.literal(literal),
+            ExprKind::Ident(name) => self.out.push_str(&name.name),
+            ExprKind::Call(callee, args) => {
+                self.operand(callee, precedence(callee) < ATOM);
+                self.out.push('(');
+                self.exprs(args);
+                self.out.push(')');
+            }
+            ExprKind::Index(target, index) => {
+                self.operand(target, precedence(target) < ATOM);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push(']');
+            }
+            ExprKind::List(items) => {
+                self.out.push('[');
+                self.exprs(items);
+                self.out.push(']');
+            }
+            ExprKind::Map(entries) => {
+                self.out.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(key);
+                    self.out.push_str(": ");
+                    self.expr(value);
+                }
+                self.out.push('}');
+            }
+            ExprKind::Field(target, field) => {
+                self.operand(target, precedence(target) < ATOM);
+                self.out.push('.');
+                self.out.push_str(&field.name);
+            }
+            ExprKind::Struct(name, fields) if fields.is_empty() => {
+                let text = format!("{} {{}}", name.name);
+                self.out.push_str(&if self.in_condition { format!("({})", text) } else { text });
+            }
+            ExprKind::Struct(name, fields) => {
+                self.out.push_str(&format!("{} {{ ", name.name));
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str(&format!("{}: ", field.name));
+                    self.expr(value);
+                }
+                self.out.push_str(" }");
+            }
+            ExprKind::Match(value, arms) => {
+                self.out.push_str("match ");
+                let in_condition = self.in_condition;
+                self.condition(value);
+                if arms.is_empty() {
+                    self.out.push_str(" {}");
+                    self.in_condition = in_condition;
+                    return;
+                }
+
+                self.out.push_str(" {\n");
+                self.depth += 1;
+                for arm in arms {
+                    self.out.push_str(&INDENT.repeat(self.depth));
+                    self.pattern(&arm.pattern);
+                    self.out.push_str(" => ");
+                    self.expr(&arm.body);
+                    self.out.push_str(",\n");
+                }
+                self.depth -= 1;
+                self.out.push_str(&INDENT.repeat(self.depth));
+                self.out.push('}');
+                self.in_condition = in_condition;
+            }
+            ExprKind::Lambda(args, body) => {
+                let args: Vec<&str> = args.iter().map(|arg| arg.name.as_str()).collect();
+                self.out.push_str(&format!("fn({}) ", args.join(", ")));
+                self.block(body);
+            }
+            ExprKind::Unary(op, operand) => {
+                self.out.push_str(match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Not => "!",
+                });
+                // `- -x` reads better than `--x`.
+                if matches!(operand.kind, ExprKind::Unary(inner, _) if inner == *op) {
+                    self.out.push(' ');
+                }
+                self.operand(operand, precedence(operand) < UNARY);
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                // Operators are left associative, so an equal right operand needs parentheses.
+                let prec = binary_precedence(*op);
+                self.operand(lhs, precedence(lhs) < prec);
+                self.out.push_str(&format!(" {} ", binary_symbol(*op)));
+                self.operand(rhs, precedence(rhs) <= prec);
+            }
+            ExprKind::Range(start, end) => {
+                self.operand(start, precedence(start) == RANGE);
+                self.out.push_str("..");
+                self.operand(end, precedence(end) == RANGE);
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Literal(literal) => self.literal(literal),
+            PatternKind::Wildcard => self.out.push('_'),
+            PatternKind::Binding(name) => self.out.push_str(&name.name),
+            PatternKind::Variant(name, fields) => {
+                self.out.push_str(&name.name);
+                self.out.push('(');
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.pattern(field);
+                }
+                self.out.push(')');
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+
+    fn operand(&mut self, expr: &Expr, parenthesize: bool) {
+        if parenthesize {
+            self.out.push('(');
+            self.expr(expr);
+            self.out.push(')');
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        match literal {
+            Literal::Int(value) => self.out.push_str(&value.to_string()),
+            // `{:?}` keeps the `.0` and prints the shortest text that reads back exactly.
+            Literal::Float(value) => self.out.push_str(&format!("{:?}", value)),
+            Literal::Bool(value) => self.out.push_str(&value.to_string()),
+            Literal::String(value) => {
+                self.out.push('"');
+                for c in value.chars() {
+                    match c {
+                        '"' => self.out.push_str("\\\""),
+                        '\\' => self.out.push_str("\\\\"),
+                        '\n' => self.out.push_str("\\n"),
+                        '\t' => self.out.push_str("\\t"),
+                        c if c.is_control() => self.out.push_str(&format!("\\u{{{:X}}}", c as u32)),
+                        c => self.out.push(c),
+                    }
+                }
+                self.out.push('"');
+            }
+        }
+    }
+}
+
+const RANGE: u8 = 0;
+const UNARY: u8 = 4;
+const ATOM: u8 = 5;
+
+/// One above the binding strength used by `Parser::binary`, as ranges bind
+/// looser than every operator.
+fn binary_precedence(op: BinOp) -> u8 {
+    match op {
+        BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 1,
+        BinOp::Add | BinOp::Sub => 2,
+        BinOp::Mul | BinOp::Div | BinOp::Mod => 3,
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match &expr.kind {
+        ExprKind::Range(..) => RANGE,
+        ExprKind::Binary(op, _, _) => binary_precedence(*op),
+        ExprKind::Unary(..) => UNARY,
+        _ => ATOM,
+    }
+}
+
+fn binary_symbol(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Mod => "%",
+        BinOp::Eq => "==",
+        BinOp::Ne => "!=",
+        BinOp::Lt => "<",
+        BinOp::Le => "<=",
+        BinOp::Gt => ">",
+        BinOp::Ge => ">=",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+use super::*;
+use super::super::{parser, scanner};
+
+    fn parse(text: &str) -> Program {
+        parser::Parser::new(scanner::Scanner::new(text)).parse().unwrap()
+    }
+
+    /// `{:?}` of the tree with every span blanked out.
+    fn shape(program: &Program) -> String {
+        let spans = regex::Regex::new(r"Span \{[^}]*\}").unwrap();
+        spans.replace_all(&format!("{:?}", program), "Span").to_string()
+    }
+
+    /// Programs from the scanner, parser and evaluator tests.
+    const CORPUS: [&str; 15] = [
+        r#"
+            fn main() {
+                print("Hello World");
+            }
+        "#,
+        r#"
+            fn main() {
+                fizzbuzz(1, 100)
+            }
+
+            fn fizzbuzz(start, end) {
+                i = start;
+                while(true){
+                    if (i < end) {
+                    break;
+                    }
+                    if(i % 15 == 0){
+                        print("FizzBuzz");
+                    }else if(i % 3 == 0){
+                        print("Fizz");
+                    }else if(i % 5 == 0){
+                        print("Buzz");
+                    }else {
+                        print(i);
+                    }
+                    i = i + 1;
+                }
+            }
+        "#,
+        "fn main() {\n    print(\"é\");\n}",
+        "/// Prints a greeting.\n///\n///Twice.\n/// /x\nfn main() {}",
+        "fn main() { a = [1, [], {}, {\"k\": [x], 2: -y}]; a[0] = a[1][a[2]]; {\"a\": 1}[\"a\"]; f()[0](1)[2] = 3; }",
+        "/// A point.\nstruct Point { x, y }\nstruct Unit {}\nimpl Point {\n    /// Length.\n    fn norm(self) { return self.x * self.x; }\n    fn add(self, other) { return Point { x: self.x + other.x, y: -self.y.z }; }\n}\nfn main() { p = Point { x: 1, y: Unit {} }; p.y.z = f(p)[0].x; if p == (Unit {}) { while Unit { x: 1 }.x {} } }",
+        "/// Shapes.\nenum Shape { Circle(r), Rect(w, h), Empty }\nfn main() { match match s { Circle(r) => r, _ => 0 } { -1 => \"neg\", 1.5 => true, x => match x {}, }; x = match Rect(1, 2) { Rect(_, Circle(h)) => h, Empty => {}, }; }",
+        "fn main() { for i in 0..n + 1 { if i == 2 { continue; } for x in (a..b)..(c == d) { break; } } for p in (Items {}) { print((0..1) == x, -(0..1)); } }",
+        "fn main() { f = fn(x) { return fn(y) { return x + y; }; }; print(f(1)(2), -fn() {}()); }",
+        "/// Shared.\nconst LIMIT = 10 * 2;\nlet count = -1;\nfn main() { count = count + LIMIT; }\nlet last = \"x\";",
+        r#"fn main() { print("a\"b\\c\n\td\u{41}\u{540D}\u{7}"); }"#,
+        "fn f() { g(0, 42, 1_000_000, 0x1F, 0o17, 0b1010_1010, 3.25, 1e3, 2.5E-3, 6_0.0_1e+1_0, 9223372036854775807, 1e-7, 1.0); }",
+        "fn f(_, __x, user_id, x_1, 名前, café, Ωmega, if_) { fn_(_); }",
+        "/// doc\nfn f(x_1, 名前) { /* a /* b */ */ return 0x1F + 1_0.5e3; }\nfn main() {}\n// end\n",
+        r#"
+            fn main() {
+                return fib(10) + sum(4);
+            }
+
+            fn fib(n) {
+                if n < 2 { return n; } else { if n > 100 { return; } }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn sum(n) {
+                total = 0;
+                while n > 0 {
+                    total = total + n;
+                    n = n - 1;
+                }
+                return -total * -(n + 1) - - -1 + !(1 < 2) == !!false;
+            }
+        "#,
+    ];
+
+    #[test]
+    fn print_round_trips() {
+        for text in CORPUS {
+            let ast = parse(text);
+            let printed = print(&ast);
+
+            assert_eq!(shape(&parse(&printed)), shape(&ast), "{}", printed);
+            assert_eq!(print(&parse(&printed)), printed);
+        }
+    }
+
+    #[test]
+    fn print_canonical_source() {
+        let ast = parse(CORPUS[1]);
+
+        assert_eq!(print(&ast), concat!(
+            "fn main() {\n",
+            "    fizzbuzz(1, 100);\n",
+            "}\n",
+            "\n",
+            "fn fizzbuzz(start, end) {\n",
+            "    i = start;\n",
+            "    while true {\n",
+            "        if i < end {\n",
+            "            break;\n",
+            "        }\n",
+            "        if i % 15 == 0 {\n",
+            "            print(\"FizzBuzz\");\n",
+            "        } else if i % 3 == 0 {\n",
+            "            print(\"Fizz\");\n",
+            "        } else if i % 5 == 0 {\n",
+            "            print(\"Buzz\");\n",
+            "        } else {\n",
+            "            print(i);\n",
+            "        }\n",
+            "        i = i + 1;\n",
+            "    }\n",
+            "}\n",
+        ));
+    }
+
+    #[test]
+    fn print_parenthesizes_only_where_needed() {
+        let cases = [
+            ("(1 + 2) * 3", "(1 + 2) * 3"),
+            ("1 + (2 * 3)", "1 + 2 * 3"),
+            ("(10 - 3) - 2", "10 - 3 - 2"),
+            ("10 - (3 - 2)", "10 - (3 - 2)"),
+            ("(a < b) == (c < d)", "a < b == (c < d)"),
+            ("-(x) * -(y + 1)", "-x * -(y + 1)"),
+            ("- -1", "- -1"),
+            ("!(a == b)", "!(a == b)"),
+            ("f((1), g(-x))", "f(1, g(-x))"),
+        ];
+
+        for (text, expected) in cases {
+            let ast = parse(&format!("fn f() {{ {}; }}", text));
+            let Item::FuncDef(func_def) = &ast[0] else { panic!() };
+            let StmtKind::Expr(expr) = &func_def.body[0].kind else { panic!() };
+            assert_eq!(print_expr(expr), expected, "{}", text);
+        }
+    }
+}
@@ -1,168 +1,737 @@
-use regex::Regex;
+use std::fmt;
+use std::ops::Range;
+
+use unicode_ident::{is_xid_continue, is_xid_start};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
     FuncDef,
     ParenthesOpen,
     ParenthesClose,
+    BracketOpen,
+    BracketClose,
     Begin,
     End,
     While,
+    For,
+    In,
     If,
     Else,
     Break,
+    Continue,
     Return,
+    Const,
+    Let,
+    Struct,
+    Impl,
+    Enum,
+    Match,
     OpRel,
     OpAdd,
     OpMul,
-    Mul,
-    Div,
-    Mod,
+    Not,
     Assign,
+    FatArrow,
     Comma,
+    Colon,
+    Dot,
+    DotDot,
     Semicolon,
     Bool,
     Int,
+    Float,
     String,
     Ident,
-    CallFunc,
+    Error,
+    Eof,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            TokenKind::FuncDef => "`fn`",
+            TokenKind::ParenthesOpen => "`(`",
+            TokenKind::ParenthesClose => "`)`",
+            TokenKind::BracketOpen => "`[`",
+            TokenKind::BracketClose => "`]`",
+            TokenKind::Begin => "`{`",
+            TokenKind::End => "`}`",
+            TokenKind::While => "`while`",
+            TokenKind::For => "`for`",
+            TokenKind::In => "`in`",
+            TokenKind::If => "`if`",
+            TokenKind::Else => "`else`",
+            TokenKind::Break => "`break`",
+            TokenKind::Continue => "`continue`",
+            TokenKind::Return => "`return`",
+            TokenKind::Const => "`const`",
+            TokenKind::Let => "`let`",
+            TokenKind::Struct => "`struct`",
+            TokenKind::Impl => "`impl`",
+            TokenKind::Enum => "`enum`",
+            TokenKind::Match => "`match`",
+            TokenKind::OpRel => "comparison operator",
+            TokenKind::OpAdd => "`+` or `-`",
+            TokenKind::OpMul => "`*`, `/` or `%`",
+            TokenKind::Not => "`!`",
+            TokenKind::Assign => "`=`",
+            TokenKind::FatArrow => "`=>`",
+            TokenKind::Comma => "`,`",
+            TokenKind::Colon => "`:`",
+            TokenKind::Dot => "`.`",
+            TokenKind::DotDot => "`..`",
+            TokenKind::Semicolon => "`;`",
+            TokenKind::Bool => "boolean",
+            TokenKind::Int => "integer",
+            TokenKind::Float => "float",
+            TokenKind::String => "string literal",
+            TokenKind::Ident => "identifier",
+            TokenKind::Error => "invalid token",
+            TokenKind::Eof => "end of input",
+        };
+
+        write!(f, "{}", text)
+    }
+}
+
+/// Location of a token in the source text.
+///
+/// `start` and `end` are byte offsets (`end` is exclusive), `line` and
+/// `column` are 1-based and point at the first character of the token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// Span from the start of `self` to the end of `other`.
+    pub fn to(&self, other: Span) -> Span {
+        Span { end: other.end, ..*self }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+    DocComment,
+}
+
+/// Source text that is not a token itself but belongs to the following token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
     pub value: String,
+    pub span: Span,
+    pub trivia: Vec<Trivia>,
 }
 
-#[derive(Debug)]
-pub struct Scanner {
-    tokens: Vec<Token>,
-    pos: usize
+impl Token {
+    /// The source text this token was lexed from.
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.span.start..self.span.end]
+    }
+
+    /// Text of the `///` comments right before this token, one line per comment.
+    pub fn doc_comment(&self) -> Option<String> {
+        let lines: Vec<&str> = self.trivia.iter()
+            .filter(|trivia| trivia.kind == TriviaKind::DocComment)
+            .map(|trivia| {
+                let line = &trivia.text[3..];
+                line.strip_prefix(' ').unwrap_or(line)
+            })
+            .collect();
+
+        if lines.is_empty() {
+            return None;
+        }
+
+        Some(lines.join("\n"))
+    }
 }
 
-impl Scanner {
-    pub fn new(text: &str) -> Self {
-        Scanner { tokens: split(text), pos: 0}
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Lexical Error at line {}, column {}: {}", self.span.line, self.span.column, self.message)
     }
+}
 
-    pub fn is_not_end(&self) -> bool {
-        self.pos < self.tokens.len() -1
+/// Single-pass lexer that yields tokens lazily.
+///
+/// A lossless lexer keeps whitespace and comments as trivia of the following
+/// token and ends with a `TokenKind::Eof` token holding the trailing trivia,
+/// so every byte of the input belongs to exactly one token or trivia piece.
+#[derive(Debug, Clone)]
+pub struct Lexer<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+    column: usize,
+    errors: Vec<LexError>,
+    lossless: bool,
+    finished: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Lexer { text, pos: 0, line: 1, column: 1, errors: Vec::new(), lossless: false, finished: false }
     }
 
-    pub fn next(&mut self) -> Token {
-        self.pos += 1;
-        self.tokens.get(self.pos).unwrap().clone()
+    pub fn lossless(text: &'a str) -> Self {
+        Lexer { lossless: true, ..Lexer::new(text) }
     }
 
-    pub fn peek(&self) -> Token {
-        self.tokens.get(self.pos).unwrap().clone()
+    /// Lexical errors found in the tokens yielded so far.
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
     }
-}
 
-fn tokenize(word: &str) -> Token { 
-    match word {
-        "fn" => Token {kind: TokenKind::FuncDef, value: word.to_string()},
-        "(" => Token {kind: TokenKind::ParenthesOpen, value: word.to_string()},
-        ")" => Token {kind: TokenKind::ParenthesClose, value: word.to_string()},
-        "{" => Token {kind: TokenKind::Begin, value: word.to_string()},
-        "}" => Token {kind: TokenKind::End, value: word.to_string()},
-        "while" => Token {kind: TokenKind::While, value: word.to_string()},
-        "if" => Token {kind: TokenKind::If, value: word.to_string()},
-        "else" => Token {kind: TokenKind::Else, value: word.to_string()},
-        "break" => Token {kind: TokenKind::Break, value: word.to_string()},
-        "return" => Token {kind: TokenKind::Return, value: word.to_string()},
-        "==" | ">" | "<" | ">=" | "<=" | "!=" =>
-            Token {kind: TokenKind::OpRel, value: word.to_string()},
-        "+" | "-" => Token {kind: TokenKind::OpAdd, value: word.to_string()},
-        "*" | "/" | "%" => Token {kind: TokenKind::OpMul, value: word.to_string()},
-        "=" => Token {kind: TokenKind::Assign, value: word.to_string()},
-        ";" => Token {kind: TokenKind::Semicolon, value: word.to_string()},
-        "," => Token {kind: TokenKind::Comma, value: word.to_string()},
-        "true" => Token {kind: TokenKind::Bool, value: word.to_string()},
-        "false" => Token {kind: TokenKind::Bool, value: word.to_string()},
-        x =>{
-            if Regex::new(r"\d").unwrap().is_match(word) {
-                return Token {kind: TokenKind::Int, value: word.to_string()};
+    fn error(&mut self, message: String, start: Span) {
+        self.errors.push(LexError { message, span: Span { end: self.pos, ..start } });
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn here(&self) -> Span {
+        Span { start: self.pos, end: self.pos, line: self.line, column: self.column }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek_char()?;
+        self.pos += c.len_utf8();
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn bump_while(&mut self, predicate: impl Fn(char) -> bool) {
+        while self.peek_char().is_some_and(&predicate) {
+            self.bump();
+        }
+    }
+
+    /// Skips whitespace and comments, collecting doc comments on the way.
+    /// In lossless mode every skipped piece is collected.
+    fn skip_trivia(&mut self) -> Vec<Trivia> {
+        let mut trivia = Vec::new();
+
+        loop {
+            let start = self.here();
+
+            let kind = if self.peek_char().is_some_and(char::is_whitespace) {
+                self.bump_while(char::is_whitespace);
+                TriviaKind::Whitespace
+            } else if self.rest().starts_with("//") {
+                self.bump_while(|c| c != '\n');
+
+                let text = &self.text[start.start..self.pos];
+                if text.starts_with("///") && !text.starts_with("////") {
+                    TriviaKind::DocComment
+                } else {
+                    TriviaKind::LineComment
+                }
+            } else if self.rest().starts_with("/*") {
+                self.skip_block_comment(start);
+                TriviaKind::BlockComment
+            } else {
+                return trivia;
+            };
+
+            if self.lossless || kind == TriviaKind::DocComment {
+                trivia.push(Trivia {
+                    kind,
+                    text: self.text[start.start..self.pos].to_string(),
+                    span: Span { end: self.pos, ..start },
+                });
             }
+        }
+    }
 
-            match x.chars().next().unwrap() {
-                '"' => Token {kind: TokenKind::String, value: word.to_string()},
-                _ => Token {kind: TokenKind::Ident, value: word.to_string()}
+    /// Reads a string literal after its opening quote and decodes the escapes.
+    /// The returned value keeps the surrounding quotes.
+    fn string(&mut self, start: Span) -> String {
+        let mut value = String::from('"');
+
+        loop {
+            let escape = self.here();
+            match self.bump() {
+                None => {
+                    let quote = Span { end: start.start + 1, ..start };
+                    self.errors.push(LexError { message: "unterminated string literal".to_string(), span: quote });
+                    break;
+                }
+                Some('"') => break,
+                Some('\\') => match self.bump() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('\\') => value.push('\\'),
+                    Some('"') => value.push('"'),
+                    Some('u') => match self.unicode_escape() {
+                        Some(c) => value.push(c),
+                        None => self.error("invalid unicode escape, expected `\\u{XXXX}`".to_string(), escape),
+                    },
+                    Some(c) => self.error(format!("unknown escape sequence `\\{}`", c), escape),
+                    None => continue,
+                },
+                Some(c) => value.push(c),
             }
+        }
 
+        value.push('"');
+        value
+    }
+
+    /// Reads the `{XXXX}` part of a `\u{XXXX}` escape.
+    fn unicode_escape(&mut self) -> Option<char> {
+        if self.peek_char() != Some('{') {
+            return None;
+        }
+        self.bump();
 
-        } 
+        let digits_start = self.pos;
+        self.bump_while(|c| c.is_ascii_hexdigit());
+        let digits = &self.text[digits_start..self.pos];
+
+        if self.peek_char() != Some('}') || digits.is_empty() || digits.len() > 6 {
+            return None;
+        }
+        self.bump();
+
+        u32::from_str_radix(digits, 16).ok().and_then(char::from_u32)
+    }
+
+    /// Reads a numeric literal whose first digit has already been consumed.
+    ///
+    /// Ints are returned in decimal and floats without digit separators, so
+    /// the value can be handed to `str::parse` as is.
+    fn number(&mut self, start: Span) -> (TokenKind, String) {
+        let radix = match (&self.text[start.start..self.pos], self.peek_char()) {
+            ("0", Some('x')) => 16,
+            ("0", Some('o')) => 8,
+            ("0", Some('b')) => 2,
+            _ => 10,
+        };
+
+        if radix != 10 {
+            self.bump();
+            let digits_start = self.pos;
+            self.bump_while(is_xid_continue);
+            let digits = self.text[digits_start..self.pos].replace('_', "");
+
+            if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+                self.error(format!("invalid digit `{}` in base {} literal", c, radix), start);
+            } else if digits.is_empty() {
+                self.error("missing digits after the base prefix".to_string(), start);
+            } else {
+                return (TokenKind::Int, self.int(&digits, radix, start));
+            }
+
+            return (TokenKind::Int, self.text[start.start..self.pos].to_string());
+        }
+
+        let is_digit_or_separator = |c: char| c.is_ascii_digit() || c == '_';
+        let mut kind = TokenKind::Int;
+        self.bump_while(is_digit_or_separator);
+
+        let mut rest = self.rest().chars();
+        if rest.next() == Some('.') && rest.next().is_some_and(|c| c.is_ascii_digit()) {
+            kind = TokenKind::Float;
+            self.bump();
+            self.bump_while(is_digit_or_separator);
+        }
+
+        let mut rest = self.rest().chars();
+        if matches!(rest.next(), Some('e' | 'E')) {
+            let after = match rest.next() {
+                Some('+' | '-') => rest.next(),
+                c => c,
+            };
+            if after.is_some_and(|c| c.is_ascii_digit()) {
+                kind = TokenKind::Float;
+                self.bump();
+                if matches!(self.peek_char(), Some('+' | '-')) {
+                    self.bump();
+                }
+                self.bump_while(is_digit_or_separator);
+            }
+        }
+
+        let literal_end = self.pos;
+        self.bump_while(is_xid_continue);
+        if self.pos != literal_end {
+            let suffix = &self.text[literal_end..self.pos];
+            self.error(format!("invalid suffix `{}` on numeric literal", suffix), start);
+            return (kind, self.text[start.start..self.pos].to_string());
+        }
+
+        let digits = self.text[start.start..self.pos].replace('_', "");
+        if kind == TokenKind::Float {
+            if digits.parse::<f64>().map_or(true, f64::is_infinite) {
+                self.error("float literal is out of range".to_string(), start);
+            }
+            return (kind, digits);
+        }
+
+        (kind, self.int(&digits, 10, start))
+    }
+
+    fn int(&mut self, digits: &str, radix: u32, start: Span) -> String {
+        match i64::from_str_radix(digits, radix) {
+            Ok(value) => value.to_string(),
+            Err(_) => {
+                self.error("integer literal is too large".to_string(), start);
+                self.text[start.start..self.pos].to_string()
+            }
+        }
+    }
+
+    /// Skips a `/* */` comment. Block comments nest.
+    fn skip_block_comment(&mut self, start: Span) {
+        let mut depth = 0;
+
+        while !self.rest().is_empty() {
+            if self.rest().starts_with("/*") {
+                depth += 1;
+                self.bump();
+            } else if self.rest().starts_with("*/") {
+                depth -= 1;
+                self.bump();
+                if depth == 0 {
+                    self.bump();
+                    return;
+                }
+            }
+            self.bump();
+        }
+
+        let opener = Span { end: start.start + 2, ..start };
+        self.errors.push(LexError { message: "unterminated block comment".to_string(), span: opener });
     }
 }
 
-fn split(text: &str) -> Vec<Token> {
-    let mut tokens: Vec<Token> = Vec::new();
-    let text_chars: Vec<char> = text.chars().collect();
-    let mut idx: usize = 0;
+impl Iterator for Lexer<'_> {
+    type Item = Token;
 
-    for (i, c) in text_chars.iter().enumerate() {
-        if idx > i {
-            continue
+    /// Yields the next token. Tokens that caused a lexical error come out as
+    /// `TokenKind::Error` and lexing carries on after them.
+    fn next(&mut self) -> Option<Token> {
+        if self.finished {
+            return None;
         }
 
-        let mut str: Vec<char> = Vec::new();
+        let trivia = self.skip_trivia();
+        let errors = self.errors.len();
+
+        let start = self.here();
+        let Some(c) = self.bump() else {
+            self.finished = true;
+            return self.lossless.then(|| Token { trivia, ..self.eof() });
+        };
+
+        let (kind, value) = match c {
+            '"' => (TokenKind::String, self.string(start)),
+            '0'..='9' => self.number(start),
+            '=' | '<' | '>' | '!' => {
+                if self.peek_char() == Some('=') || (c == '=' && self.peek_char() == Some('>')) {
+                    self.bump();
+                }
+                self.symbol(start)
+            }
+            '.' => {
+                if self.peek_char() == Some('.') {
+                    self.bump();
+                }
+                self.symbol(start)
+            }
+            c if c == '_' || is_xid_start(c) => {
+                self.bump_while(is_xid_continue);
+                let word = &self.text[start.start..self.pos];
+                (tokenize(word), word.to_string())
+            }
+            _ => self.symbol(start),
+        };
 
-        if text_chars[idx] == '"' {
-            str.push(*c);
-            idx += 1;
+        let kind = if self.errors.len() > errors { TokenKind::Error } else { kind };
+        Some(Token { kind, value, span: Span { end: self.pos, ..start }, trivia })
+    }
+}
+
+impl Lexer<'_> {
+    /// Classifies the operator or punctuation that has just been consumed.
+    fn symbol(&mut self, start: Span) -> (TokenKind, String) {
+        let text = &self.text[start.start..self.pos];
 
-            while text_chars[idx] != '"' {
-                str.push(text_chars[idx]);
-                idx += 1;
+        match tokenize(text) {
+            TokenKind::Ident => {
+                let message = format!("unexpected character `{}`", text);
+                self.error(message, start);
+                (TokenKind::Error, text.to_string())
             }
+            kind => (kind, text.to_string()),
+        }
+    }
+
+    fn eof(&self) -> Token {
+        Token { kind: TokenKind::Eof, value: "".to_string(), span: self.here(), trivia: Vec::new() }
+    }
+}
 
-            str.push('"');
-            idx += 1;
-            let token = tokenize(&str.iter().collect::<String>());
-            tokens.push(token);
-        } else if Regex::new(r"(=|<|>|!)").unwrap().is_match(&c.to_string()) {
-            str.push(*c);
-            idx += 1;
-
-            if text_chars[idx] == '=' {
-                str.push(text_chars[idx]);
-                idx += 1;
+/// A text edit: the bytes in `range` of the old text were replaced by `text`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    pub fn apply(&self, text: &str) -> String {
+        let mut edited = text.to_string();
+        edited.replace_range(self.range.clone(), &self.text);
+        edited
+    }
+}
+
+/// How many bytes past its end the lexer may look before finishing a token
+/// (`1e+5` checks `e`, `+` and `5` after the `1`).
+const LOOKAHEAD: usize = 3;
+
+impl Lexer<'_> {
+    /// Re-lexes the text after `edit`, reusing `tokens` lexed from the text
+    /// before it. The lexer must be fresh, in the same mode that produced
+    /// `tokens`, and over the edited text. The result equals a full re-scan.
+    /// Afterwards `errors` holds the lexical errors of the re-lexed tokens;
+    /// those of reused tokens are not reported again.
+    pub fn relex(&mut self, tokens: &[Token], edit: &Edit) -> Vec<Token> {
+        let keep = tokens.partition_point(|token| token.span.end + LOOKAHEAD <= edit.range.start);
+        let mut result = tokens[..keep].to_vec();
+
+        if let Some(last) = result.last() {
+            self.pos = last.span.start;
+            self.line = last.span.line;
+            self.column = last.span.column;
+            while self.pos < last.span.end {
+                self.bump();
             }
+        }
 
-            let token = tokenize(&str.iter().collect::<String>());
-            tokens.push(token);
-        } else if Regex::new(r"\d").unwrap().is_match(&c.to_string()) {
-            while idx < text_chars.len() && Regex::new(r"\d").unwrap().is_match(&text_chars[idx].to_string()) {
-                str.push(text_chars[idx]);
-                idx += 1;
+        let edit_end = edit.range.start + edit.text.len();
+        let shift = |span: Span, lines: usize, old_lines: usize| Span {
+            start: span.start - edit.range.end + edit_end,
+            end: span.end - edit.range.end + edit_end,
+            line: span.line + lines - old_lines,
+            column: span.column,
+        };
+
+        while let Some(token) = self.next() {
+            result.push(token);
+
+            // Once a token ends where an old one did, after the edit and on a
+            // later line, lexing continues exactly as before. The remaining old
+            // tokens only need their byte offsets and lines moved.
+            if self.pos < edit_end || !self.text[edit_end..self.pos].contains('\n') {
+                continue;
+            }
+            let boundary = self.pos - edit_end + edit.range.end;
+            let resync = tokens.partition_point(|token| token.span.end < boundary);
+            if tokens.get(resync).is_none_or(|token| token.span.end != boundary) {
+                continue;
             }
 
-            let token = tokenize(&str.iter().collect::<String>());
-            tokens.push(token);
-        } else if Regex::new(r"[a-z|A-Z]").unwrap().is_match(&c.to_string()) {
-            while idx < text_chars.len() && Regex::new(r"[a-z|A-Z]|[0-9]").unwrap().is_match(&text_chars[idx].to_string()) {
-                str.push(text_chars[idx]);
-                idx += 1;
+            let Some(next) = self.next() else { break };
+            let lines = next.span.line;
+            result.push(next);
+            let Some(old) = tokens.get(resync + 1) else { break };
+            let old_lines = old.span.line;
+
+            for token in &tokens[resync + 2..] {
+                let mut token = token.clone();
+                token.span = shift(token.span, lines, old_lines);
+                for trivia in &mut token.trivia {
+                    trivia.span = shift(trivia.span, lines, old_lines);
+                }
+                result.push(token);
             }
+            break;
+        }
 
-            let token = tokenize(&str.iter().collect::<String>());
-            tokens.push(token);
-        } else if text_chars[idx] == ' ' || text_chars[idx] == '\n' {
-            idx += 1;
-        } else {
-            let token = tokenize(&c.to_string());
-            tokens.push(token);
-            idx += 1;
+        result
+    }
+}
+
+/// Lexes the whole text, returning every lexical error if there is any.
+pub fn scan(text: &str) -> Result<Vec<Token>, Vec<LexError>> {
+    let mut lexer = Lexer::new(text);
+    let tokens: Vec<Token> = lexer.by_ref().collect();
+
+    if lexer.errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(lexer.errors)
+    }
+}
+
+/// Token cursor used by the parser. Tokens are pulled from the lexer on demand
+/// and the last token is always `TokenKind::Eof`.
+#[derive(Debug)]
+pub struct Scanner<'a> {
+    lexer: Lexer<'a>,
+    tokens: Vec<Token>,
+    pos: usize
+}
+
+/// A saved scanner position, see `Scanner::mark`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mark(usize);
+
+impl<'a> Scanner<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Scanner::from_lexer(Lexer::new(text))
+    }
+
+    /// Scanner over a lossless lexer, see `Lexer::lossless`.
+    pub fn lossless(text: &'a str) -> Self {
+        Scanner::from_lexer(Lexer::lossless(text))
+    }
+
+    fn from_lexer(lexer: Lexer<'a>) -> Self {
+        let mut scanner = Scanner { lexer, tokens: Vec::new(), pos: 0};
+        scanner.fill(0);
+        scanner
+    }
+
+    /// Lexical errors found in the tokens read so far.
+    pub fn errors(&self) -> &[LexError] {
+        self.lexer.errors()
+    }
+
+    /// Buffers tokens up to `ahead` tokens after the current one, or up to `TokenKind::Eof`.
+    fn fill(&mut self, ahead: usize) {
+        while self.tokens.len() <= self.pos + ahead {
+            if self.tokens.last().is_some_and(|token| token.kind == TokenKind::Eof) {
+                break;
+            }
+
+            let token = self.lexer.next().unwrap_or_else(|| self.lexer.eof());
+            self.tokens.push(token);
+        }
+    }
+
+    /// Whether the current token is `TokenKind::Eof`.
+    pub fn is_end(&self) -> bool {
+        self.tokens[self.pos].kind == TokenKind::Eof
+    }
+
+    /// Advances to the next token and returns it. Stays on `TokenKind::Eof`
+    /// once the input is exhausted.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Token {
+        if !self.is_end() {
+            self.pos += 1;
+            self.fill(0);
         }
+        self.peek()
+    }
+
+    pub fn peek(&self) -> Token {
+        self.tokens[self.pos].clone()
     }
 
-    tokens
+    /// The token `k` positions after the current one; `peek_nth(0)` is `peek()`.
+    /// Looking past the end gives `TokenKind::Eof`.
+    pub fn peek_nth(&mut self, k: usize) -> Token {
+        self.fill(k);
+        self.tokens[(self.pos + k).min(self.tokens.len() - 1)].clone()
+    }
+
+    /// Saves the current position so the parser can come back to it with `reset`.
+    pub fn mark(&self) -> Mark {
+        Mark(self.pos)
+    }
+
+    /// Rewinds (or fast-forwards) to a position saved by `mark`.
+    pub fn reset(&mut self, mark: Mark) {
+        self.pos = mark.0;
+    }
+}
+
+/// Classifies keywords and punctuation. Anything else is an identifier.
+fn tokenize(word: &str) -> TokenKind {
+    match word {
+        "fn" => TokenKind::FuncDef,
+        "(" => TokenKind::ParenthesOpen,
+        ")" => TokenKind::ParenthesClose,
+        "[" => TokenKind::BracketOpen,
+        "]" => TokenKind::BracketClose,
+        "{" => TokenKind::Begin,
+        "}" => TokenKind::End,
+        "while" => TokenKind::While,
+        "for" => TokenKind::For,
+        "in" => TokenKind::In,
+        "if" => TokenKind::If,
+        "else" => TokenKind::Else,
+        "break" => TokenKind::Break,
+        "continue" => TokenKind::Continue,
+        "return" => TokenKind::Return,
+        "const" => TokenKind::Const,
+        "let" => TokenKind::Let,
+        "struct" => TokenKind::Struct,
+        "impl" => TokenKind::Impl,
+        "enum" => TokenKind::Enum,
+        "match" => TokenKind::Match,
+        "==" | ">" | "<" | ">=" | "<=" | "!=" => TokenKind::OpRel,
+        "+" | "-" => TokenKind::OpAdd,
+        "*" | "/" | "%" => TokenKind::OpMul,
+        "!" => TokenKind::Not,
+        "=" => TokenKind::Assign,
+        "=>" => TokenKind::FatArrow,
+        ";" => TokenKind::Semicolon,
+        "," => TokenKind::Comma,
+        ":" => TokenKind::Colon,
+        "." => TokenKind::Dot,
+        ".." => TokenKind::DotDot,
+        "true" => TokenKind::Bool,
+        "false" => TokenKind::Bool,
+        _ => TokenKind::Ident,
+    }
 }
 
 #[cfg(test)]
 mod tests {
 use super::*;
 
+    fn kv(token: Token) -> (TokenKind, String) {
+        (token.kind, token.value)
+    }
+
     #[test]
     fn scanner_hello_world() {
         let text = r#"
@@ -172,66 +741,459 @@ use super::*;
         "#;
         let mut scanner = Scanner::new(text);
 
-        assert!(scanner.is_not_end());
+        assert_ne!(scanner.peek_nth(1).kind, TokenKind::Eof);
 
         assert_eq!(
-            scanner.peek(),
-            Token {kind: TokenKind::FuncDef, value: "fn".to_string()},
+            kv(scanner.peek()),
+            (TokenKind::FuncDef, "fn".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "main".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "main".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Begin, value: "{".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Begin, "{".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "print".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "print".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::String, value: "\"Hello World\"".to_string()},
+            kv(scanner.next()),
+            (TokenKind::String, "\"Hello World\"".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
         
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Semicolon, value: ";".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Semicolon, ";".to_string()),
         );
 
-        assert!(scanner.is_not_end());
+        assert_ne!(scanner.peek_nth(1).kind, TokenKind::Eof);
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::End, value: "}".to_string()},
+            kv(scanner.next()),
+            (TokenKind::End, "}".to_string()),
         );
 
-        assert!(!scanner.is_not_end());
+        assert_eq!(scanner.peek_nth(1).kind, TokenKind::Eof);
+        assert_eq!(scanner.next().kind, TokenKind::Eof);
+        assert!(scanner.is_end());
+    }
+
+    #[test]
+    fn scanner_spans() {
+        let text = "fn main() {\n    print(\"é\");\n}";
+        let mut scanner = Scanner::new(text);
+
+        assert_eq!(scanner.peek().span, Span { start: 0, end: 2, line: 1, column: 1 });
+        assert_eq!(scanner.next().span, Span { start: 3, end: 7, line: 1, column: 4 });
+
+        for _ in 0..5 {
+            scanner.next();
+        }
+
+        let string = scanner.next();
+        assert_eq!(string.span, Span { start: 22, end: 26, line: 2, column: 11 });
+        assert_eq!(&text[string.span.start..string.span.end], "\"é\"");
+        assert_eq!(scanner.next().span, Span { start: 26, end: 27, line: 2, column: 14 });
+
+        scanner.next();
+        assert_eq!(scanner.next().span, Span { start: 29, end: 30, line: 3, column: 1 });
+    }
+
+    #[test]
+    fn lexer_iterates_tokens() {
+        let tokens: Vec<(TokenKind, String)> = Lexer::new("x1\t=\r\n42 >=y;").map(kv).collect();
+
+        assert_eq!(tokens, vec![
+            (TokenKind::Ident, "x1".to_string()),
+            (TokenKind::Assign, "=".to_string()),
+            (TokenKind::Int, "42".to_string()),
+            (TokenKind::OpRel, ">=".to_string()),
+            (TokenKind::Ident, "y".to_string()),
+            (TokenKind::Semicolon, ";".to_string()),
+        ]);
+
+        let tokens: Vec<TokenKind> = Lexer::new("a[0]={\"k\":[]}").map(|token| token.kind).collect();
+        assert_eq!(tokens, vec![
+            TokenKind::Ident,
+            TokenKind::BracketOpen,
+            TokenKind::Int,
+            TokenKind::BracketClose,
+            TokenKind::Assign,
+            TokenKind::Begin,
+            TokenKind::String,
+            TokenKind::Colon,
+            TokenKind::BracketOpen,
+            TokenKind::BracketClose,
+            TokenKind::End,
+        ]);
+
+        let tokens: Vec<TokenKind> = Lexer::new("match x{_=>1}").map(|token| token.kind).collect();
+        assert_eq!(tokens, vec![
+            TokenKind::Match,
+            TokenKind::Ident,
+            TokenKind::Begin,
+            TokenKind::Ident,
+            TokenKind::FatArrow,
+            TokenKind::Int,
+            TokenKind::End,
+        ]);
+
+        let tokens: Vec<TokenKind> = Lexer::new("for i in 0..n{continue}").map(|token| token.kind).collect();
+        assert_eq!(tokens, vec![
+            TokenKind::For,
+            TokenKind::Ident,
+            TokenKind::In,
+            TokenKind::Int,
+            TokenKind::DotDot,
+            TokenKind::Ident,
+            TokenKind::Begin,
+            TokenKind::Continue,
+            TokenKind::End,
+        ]);
+    }
+
+    #[test]
+    fn lexer_skips_comments() {
+        let text = r#"
+            // line comment with "quotes" and /* an opener
+            a /* block /* nested */ still comment */ b
+            /**/ c //// not a doc comment
+        "#;
+        let tokens: Vec<(TokenKind, String)> = Lexer::new(text).map(kv).collect();
+
+        assert_eq!(tokens, vec![
+            (TokenKind::Ident, "a".to_string()),
+            (TokenKind::Ident, "b".to_string()),
+            (TokenKind::Ident, "c".to_string()),
+        ]);
+        assert!(Lexer::new(text).all(|token| token.trivia.is_empty()));
+    }
+
+    #[test]
+    fn lexer_attaches_doc_comments() {
+        let text = "/// Prints a greeting.\n///\n///Twice.\nfn main() {}";
+        let mut lexer = Lexer::new(text);
+
+        let func_def = lexer.next().unwrap();
+        assert_eq!(func_def.kind, TokenKind::FuncDef);
+        assert_eq!(func_def.doc_comment(), Some("Prints a greeting.\n\nTwice.".to_string()));
+        assert_eq!(func_def.trivia[0].span, Span { start: 0, end: 22, line: 1, column: 1 });
+        assert_eq!(func_def.trivia[2].span, Span { start: 27, end: 36, line: 3, column: 1 });
+
+        assert_eq!(lexer.next().unwrap().doc_comment(), None);
+    }
+
+    #[test]
+    fn lexer_decodes_string_escapes() {
+        let text = r#"print("a\"b\\c\n\td\u{41}\u{540D}");"#;
+        let mut lexer = Lexer::new(text);
+        let string = lexer.nth(2).unwrap();
+
+        assert_eq!(string.kind, TokenKind::String);
+        assert_eq!(string.value, "\"a\"b\\c\n\tdA名\"");
+        assert_eq!(&text[string.span.start..string.span.end], &text[6..text.len() - 2]);
+        assert_eq!(kv(lexer.next().unwrap()), (TokenKind::ParenthesClose, ")".to_string()));
+        assert!(lexer.errors().is_empty());
+    }
+
+    #[test]
+    fn lexer_reports_bad_escapes() {
+        let mut lexer = Lexer::new(r#""\q \u{110000} \u41""#);
+        assert_eq!(lexer.next().unwrap().kind, TokenKind::Error);
+
+        let messages: Vec<String> = lexer.errors().iter().map(|error| error.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Lexical Error at line 1, column 2: unknown escape sequence `\\q`",
+            "Lexical Error at line 1, column 5: invalid unicode escape, expected `\\u{XXXX}`",
+            "Lexical Error at line 1, column 16: invalid unicode escape, expected `\\u{XXXX}`",
+        ]);
+    }
+
+    #[test]
+    fn lexer_reports_unterminated_string() {
+        let mut lexer = Lexer::new("print(\n  \"oops);\n");
+        let string = lexer.nth(2).unwrap();
+
+        assert_eq!(string.kind, TokenKind::Error);
+        assert_eq!(lexer.next(), None);
+        assert_eq!(lexer.errors(), &[LexError {
+            message: "unterminated string literal".to_string(),
+            span: Span { start: 9, end: 10, line: 2, column: 3 },
+        }]);
+    }
+
+    #[test]
+    fn lexer_reads_numbers() {
+        let text = "0 42 1_000_000 0x1F 0o17 0b1010_1010 3.25 1e3 2.5E-3 6_0.0_1e+1_0 9223372036854775807 1..10 7.x";
+        let tokens: Vec<(TokenKind, String)> = Lexer::new(text).map(kv).collect();
+
+        assert_eq!(tokens, vec![
+            (TokenKind::Int, "0".to_string()),
+            (TokenKind::Int, "42".to_string()),
+            (TokenKind::Int, "1000000".to_string()),
+            (TokenKind::Int, "31".to_string()),
+            (TokenKind::Int, "15".to_string()),
+            (TokenKind::Int, "170".to_string()),
+            (TokenKind::Float, "3.25".to_string()),
+            (TokenKind::Float, "1e3".to_string()),
+            (TokenKind::Float, "2.5E-3".to_string()),
+            (TokenKind::Float, "60.01e+10".to_string()),
+            (TokenKind::Int, "9223372036854775807".to_string()),
+            (TokenKind::Int, "1".to_string()),
+            (TokenKind::DotDot, "..".to_string()),
+            (TokenKind::Int, "10".to_string()),
+            (TokenKind::Int, "7".to_string()),
+            (TokenKind::Dot, ".".to_string()),
+            (TokenKind::Ident, "x".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn lexer_rejects_bad_numbers() {
+        let text = "9223372036854775808 0xFFFFFFFFFFFFFFFFF 0b102 0x 12ab 1e999";
+        let mut lexer = Lexer::new(text);
+        let values: Vec<String> = lexer.by_ref().map(|token| token.value).collect();
+
+        assert_eq!(values, vec!["9223372036854775808", "0xFFFFFFFFFFFFFFFFF", "0b102", "0x", "12ab", "1e999"]);
+
+        let messages: Vec<String> = lexer.errors().iter().map(|error| error.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Lexical Error at line 1, column 1: integer literal is too large",
+            "Lexical Error at line 1, column 21: integer literal is too large",
+            "Lexical Error at line 1, column 41: invalid digit `2` in base 2 literal",
+            "Lexical Error at line 1, column 47: missing digits after the base prefix",
+            "Lexical Error at line 1, column 50: invalid suffix `ab` on numeric literal",
+            "Lexical Error at line 1, column 55: float literal is out of range",
+        ]);
+    }
+
+    #[test]
+    fn scan_collects_every_error() {
+        let text = "fn main() {\n    a = 1 @ 2;\n    b = #;\n}\nx = $";
+        let errors = scan(text).unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+
+        assert_eq!(messages, vec![
+            "Lexical Error at line 2, column 11: unexpected character `@`",
+            "Lexical Error at line 3, column 9: unexpected character `#`",
+            "Lexical Error at line 5, column 5: unexpected character `$`",
+        ]);
+
+        let kinds: Vec<TokenKind> = Lexer::new(text).skip(8).map(|token| token.kind).take(3).collect();
+        assert_eq!(kinds, vec![TokenKind::Error, TokenKind::Int, TokenKind::Semicolon]);
+
+        assert_eq!(scan("a != b").unwrap().len(), 3);
+        assert_eq!(kv(Lexer::new("!a").next().unwrap()), (TokenKind::Not, "!".to_string()));
+    }
+
+    #[test]
+    fn scanner_stops_at_eof() {
+        let mut scanner = Scanner::new("x =");
+        assert_eq!(kv(scanner.next()), (TokenKind::Assign, "=".to_string()));
+        assert!(!scanner.is_end());
+
+        let eof = scanner.next();
+        assert_eq!(eof.kind, TokenKind::Eof);
+        assert_eq!(eof.span, Span { start: 3, end: 3, line: 1, column: 4 });
+        assert_eq!(scanner.next(), eof);
+        assert_eq!(scanner.peek(), eof);
+
+        let mut scanner = Scanner::new("  // nothing here\n");
+        assert_eq!(scanner.peek().kind, TokenKind::Eof);
+        assert_eq!(scanner.next().kind, TokenKind::Eof);
+        assert!(scanner.is_end());
+    }
+
+    #[test]
+    fn lexer_reads_unicode_identifiers() {
+        let text = "_ __x user_id x_1 名前 café Ωmega a|b 🙂 if_ fn";
+        let tokens: Vec<(TokenKind, String)> = Lexer::new(text).map(kv).collect();
+
+        assert_eq!(tokens, vec![
+            (TokenKind::Ident, "_".to_string()),
+            (TokenKind::Ident, "__x".to_string()),
+            (TokenKind::Ident, "user_id".to_string()),
+            (TokenKind::Ident, "x_1".to_string()),
+            (TokenKind::Ident, "名前".to_string()),
+            (TokenKind::Ident, "café".to_string()),
+            (TokenKind::Ident, "Ωmega".to_string()),
+            (TokenKind::Ident, "a".to_string()),
+            (TokenKind::Error, "|".to_string()),
+            (TokenKind::Ident, "b".to_string()),
+            (TokenKind::Error, "🙂".to_string()),
+            (TokenKind::Ident, "if_".to_string()),
+            (TokenKind::FuncDef, "fn".to_string()),
+        ]);
+
+        let name = Lexer::new("x = 名前;").nth(2).unwrap();
+        assert_eq!(name.span, Span { start: 4, end: 10, line: 1, column: 5 });
+    }
+
+    fn reconstruct(tokens: &[Token], text: &str) -> String {
+        let mut source = String::new();
+        for token in tokens {
+            for trivia in &token.trivia {
+                source.push_str(&trivia.text);
+            }
+            source.push_str(token.text(text));
+        }
+        source
+    }
+
+    #[test]
+    fn lossless_lexer_round_trips() {
+        let texts = [
+            "",
+            "   \n\t",
+            "fn main() {\r\n\tprint(\"a\\\"b\"); // done\n}\n",
+            "/// doc\nfn f(x_1, 名前) { /* a /* b */ */ return 0x1F + 1_0.5e3; }",
+            "x = @ \"unterminated\n/* never closed",
+            "a // trailing comment without newline",
+        ];
+
+        for text in texts {
+            let tokens: Vec<Token> = Lexer::lossless(text).collect();
+            assert_eq!(tokens.last().map(|token| &token.kind), Some(&TokenKind::Eof));
+            assert_eq!(reconstruct(&tokens, text), text);
+        }
+
+        let tokens: Vec<Token> = Lexer::lossless("a /* c */\n/// d\nb").collect();
+        let kinds: Vec<TriviaKind> = tokens[1].trivia.iter().map(|trivia| trivia.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            TriviaKind::Whitespace,
+            TriviaKind::BlockComment,
+            TriviaKind::Whitespace,
+            TriviaKind::DocComment,
+            TriviaKind::Whitespace,
+        ]);
+        assert_eq!(tokens[1].doc_comment(), Some("d".to_string()));
+    }
+
+    #[test]
+    fn lossless_scanner_keeps_trailing_trivia() {
+        let text = "fn main() {}\n// end\n";
+        let mut scanner = Scanner::lossless(text);
+        let mut tokens = vec![scanner.peek()];
+        while tokens.last().unwrap().kind != TokenKind::Eof {
+            tokens.push(scanner.next());
+        }
+
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(reconstruct(&tokens, text), text);
+        assert_eq!(Lexer::lossless(text).last(), Some(tokens[6].clone()));
+    }
+
+    #[test]
+    fn lexer_reports_unterminated_block_comment() {
+        let errors = scan("a /* b /* c */").unwrap_err();
+        assert_eq!(errors, vec![LexError {
+            message: "unterminated block comment".to_string(),
+            span: Span { start: 2, end: 4, line: 1, column: 3 },
+        }]);
+    }
+
+    #[test]
+    fn relex_matches_full_scan() {
+        let texts = [
+            "fn main() {\n    x = 1.5e+3;\n    print(\"a b\");\n}\n",
+            "/// doc\nfn f(a, b) {\n  /* c */ return a <= b; // d\n}\nfn g() { f(1, 2); }\n",
+            "a\nb\nc = \"s\"\nd /* e\n */ f\n",
+        ];
+        let replacements = ["", "x", "\"", "/*", "*/", "\n", "1e", "=", "//", "名"];
+
+        for text in texts {
+            for lossless in [false, true] {
+                let lexer = |text: &str| -> Vec<Token> {
+                    if lossless { Lexer::lossless(text).collect() } else { Lexer::new(text).collect() }
+                };
+                let relex = |text: &str, tokens: &[Token], edit: &Edit| -> Vec<Token> {
+                    let mut lexer = if lossless { Lexer::lossless(text) } else { Lexer::new(text) };
+                    lexer.relex(tokens, edit)
+                };
+                let tokens = lexer(text);
+
+                for start in (0..=text.len()).filter(|i| text.is_char_boundary(*i)) {
+                    for len in [0, 1, 3] {
+                        let end = (start + len).min(text.len());
+                        if !text.is_char_boundary(end) {
+                            continue;
+                        }
+
+                        for replacement in replacements {
+                            let edit = Edit { range: start..end, text: replacement.to_string() };
+                            let edited = edit.apply(text);
+                            assert_eq!(relex(&edited, &tokens, &edit), lexer(&edited), "{:?} in {:?}", edit, text);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn relex_reports_errors_in_the_edit() {
+        let text = "a = 1;\nb = 2;\nc = 3;\n";
+        let tokens: Vec<Token> = Lexer::new(text).collect();
+        let edit = Edit { range: 11..12, text: "\"2".to_string() };
+        let edited = edit.apply(text);
+
+        let mut lexer = Lexer::new(&edited);
+        assert_eq!(lexer.relex(&tokens, &edit), Lexer::new(&edited).collect::<Vec<Token>>());
+        assert_eq!(lexer.errors(), [LexError {
+            message: "unterminated string literal".to_string(),
+            span: Span { start: 11, end: 12, line: 2, column: 5 },
+        }]);
+    }
+
+    #[test]
+    fn scanner_looks_ahead_and_rewinds() {
+        let mut scanner = Scanner::new("f(x); g = 1;");
+
+        assert_eq!(kv(scanner.peek_nth(0)), (TokenKind::Ident, "f".to_string()));
+        assert_eq!(kv(scanner.peek_nth(1)), (TokenKind::ParenthesOpen, "(".to_string()));
+        assert_eq!(kv(scanner.peek_nth(6)), (TokenKind::Assign, "=".to_string()));
+        assert_eq!(scanner.peek_nth(9).kind, TokenKind::Eof);
+        assert_eq!(scanner.peek_nth(100).kind, TokenKind::Eof);
+        assert_eq!(kv(scanner.peek()), (TokenKind::Ident, "f".to_string()));
+
+        let start = scanner.mark();
+        scanner.next();
+        scanner.next();
+        let after_x = scanner.mark();
+        assert_eq!(kv(scanner.next()), (TokenKind::ParenthesClose, ")".to_string()));
+
+        scanner.reset(start);
+        assert_eq!(kv(scanner.peek()), (TokenKind::Ident, "f".to_string()));
+        scanner.reset(after_x);
+        assert_eq!(kv(scanner.peek()), (TokenKind::Ident, "x".to_string()));
+        assert_eq!(kv(scanner.peek_nth(3)), (TokenKind::Ident, "g".to_string()));
     }
 
     #[test]
@@ -263,516 +1225,516 @@ use super::*;
 
         let mut scanner = Scanner::new(text);
 
-        assert!(scanner.is_not_end());
+        assert_ne!(scanner.peek_nth(1).kind, TokenKind::Eof);
 
         assert_eq!(
-            scanner.peek(),
-            Token {kind: TokenKind::FuncDef, value: "fn".to_string()},
+            kv(scanner.peek()),
+            (TokenKind::FuncDef, "fn".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "main".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "main".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Begin, value: "{".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Begin, "{".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "fizzbuzz".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "fizzbuzz".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Int, value: "1".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Int, "1".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Comma, value: ",".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Comma, ",".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Int, value: "100".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Int, "100".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::End, value: "}".to_string()},
+            kv(scanner.next()),
+            (TokenKind::End, "}".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::FuncDef, value: "fn".to_string()},
+            kv(scanner.next()),
+            (TokenKind::FuncDef, "fn".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "fizzbuzz".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "fizzbuzz".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "start".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "start".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Comma, value: ",".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Comma, ",".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "end".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "end".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Begin, value: "{".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Begin, "{".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "i".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "i".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Assign, value: "=".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Assign, "=".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "start".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "start".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Semicolon, value: ";".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Semicolon, ";".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::While, value: "while".to_string()},
+            kv(scanner.next()),
+            (TokenKind::While, "while".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Bool, value: "true".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Bool, "true".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Begin, value: "{".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Begin, "{".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::If, value: "if".to_string()},
+            kv(scanner.next()),
+            (TokenKind::If, "if".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "i".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "i".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::OpRel, value: "<".to_string()},
+            kv(scanner.next()),
+            (TokenKind::OpRel, "<".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "end".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "end".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Begin, value: "{".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Begin, "{".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Break, value: "break".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Break, "break".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Semicolon, value: ";".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Semicolon, ";".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::End, value: "}".to_string()},
+            kv(scanner.next()),
+            (TokenKind::End, "}".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::If, value: "if".to_string()},
+            kv(scanner.next()),
+            (TokenKind::If, "if".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "i".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "i".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::OpMul, value: "%".to_string()},
+            kv(scanner.next()),
+            (TokenKind::OpMul, "%".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Int, value: "15".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Int, "15".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::OpRel, value: "==".to_string()},
+            kv(scanner.next()),
+            (TokenKind::OpRel, "==".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Int, value: "0".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Int, "0".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Begin, value: "{".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Begin, "{".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "print".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "print".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::String, value: "\"FizzBuzz\"".to_string()},
+            kv(scanner.next()),
+            (TokenKind::String, "\"FizzBuzz\"".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Semicolon, value: ";".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Semicolon, ";".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::End, value: "}".to_string()},
+            kv(scanner.next()),
+            (TokenKind::End, "}".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Else, value: "else".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Else, "else".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::If, value: "if".to_string()},
+            kv(scanner.next()),
+            (TokenKind::If, "if".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "i".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "i".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::OpMul, value: "%".to_string()},
+            kv(scanner.next()),
+            (TokenKind::OpMul, "%".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Int, value: "3".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Int, "3".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::OpRel, value: "==".to_string()},
+            kv(scanner.next()),
+            (TokenKind::OpRel, "==".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Int, value: "0".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Int, "0".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Begin, value: "{".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Begin, "{".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "print".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "print".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::String, value: "\"Fizz\"".to_string()},
+            kv(scanner.next()),
+            (TokenKind::String, "\"Fizz\"".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Semicolon, value: ";".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Semicolon, ";".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::End, value: "}".to_string()},
+            kv(scanner.next()),
+            (TokenKind::End, "}".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Else, value: "else".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Else, "else".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::If, value: "if".to_string()},
+            kv(scanner.next()),
+            (TokenKind::If, "if".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "i".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "i".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::OpMul, value: "%".to_string()},
+            kv(scanner.next()),
+            (TokenKind::OpMul, "%".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Int, value: "5".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Int, "5".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::OpRel, value: "==".to_string()},
+            kv(scanner.next()),
+            (TokenKind::OpRel, "==".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Int, value: "0".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Int, "0".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Begin, value: "{".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Begin, "{".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "print".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "print".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::String, value: "\"Buzz\"".to_string()},
+            kv(scanner.next()),
+            (TokenKind::String, "\"Buzz\"".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Semicolon, value: ";".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Semicolon, ";".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::End, value: "}".to_string()},
+            kv(scanner.next()),
+            (TokenKind::End, "}".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Else, value: "else".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Else, "else".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Begin, value: "{".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Begin, "{".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "print".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "print".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesOpen, value: "(".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesOpen, "(".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "i".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "i".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::ParenthesClose, value: ")".to_string()},
+            kv(scanner.next()),
+            (TokenKind::ParenthesClose, ")".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Semicolon, value: ";".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Semicolon, ";".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::End, value: "}".to_string()},
+            kv(scanner.next()),
+            (TokenKind::End, "}".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "i".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "i".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Assign, value: "=".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Assign, "=".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Ident, value: "i".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Ident, "i".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::OpAdd, value: "+".to_string()},
+            kv(scanner.next()),
+            (TokenKind::OpAdd, "+".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Int, value: "1".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Int, "1".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::Semicolon, value: ";".to_string()},
+            kv(scanner.next()),
+            (TokenKind::Semicolon, ";".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::End, value: "}".to_string()},
+            kv(scanner.next()),
+            (TokenKind::End, "}".to_string()),
         );
 
         assert_eq!(
-            scanner.next(),
-            Token {kind: TokenKind::End, value: "}".to_string()},
+            kv(scanner.next()),
+            (TokenKind::End, "}".to_string()),
         );
     }
 }
\ No newline at end of file
@@ -0,0 +1,791 @@
+//! Traversal of the AST. Each trait method defaults to walking into the
+//! node's children through the matching `walk_*` function, so a pass only
+//! overrides the nodes it cares about and calls `walk_*` to keep descending.
+
+use super::parser::{
+    Arm, Block, EnumDef, Expr, ExprKind, FuncDef, Global, Ident, Impl, Item, Literal, Pattern, PatternKind, Program, Stmt, StmtKind,
+    StructDef, Variant,
+};
+
+/// Read-only traversal.
+pub trait Visitor: Sized {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program)
+    }
+
+    fn visit_item(&mut self, item: &Item) {
+        walk_item(self, item)
+    }
+
+    fn visit_func_def(&mut self, func_def: &FuncDef) {
+        walk_func_def(self, func_def)
+    }
+
+    fn visit_global(&mut self, global: &Global) {
+        walk_global(self, global)
+    }
+
+    fn visit_struct_def(&mut self, struct_def: &StructDef) {
+        walk_struct_def(self, struct_def)
+    }
+
+    fn visit_impl(&mut self, impl_def: &Impl) {
+        walk_impl(self, impl_def)
+    }
+
+    fn visit_enum_def(&mut self, enum_def: &EnumDef) {
+        walk_enum_def(self, enum_def)
+    }
+
+    fn visit_ident(&mut self, _ident: &Ident) {}
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        walk_pattern(self, pattern)
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) {}
+}
+
+pub fn walk_program<V: Visitor>(visitor: &mut V, program: &Program) {
+    for item in program {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_item<V: Visitor>(visitor: &mut V, item: &Item) {
+    match item {
+        Item::FuncDef(func_def) => visitor.visit_func_def(func_def),
+        Item::Global(global) => visitor.visit_global(global),
+        Item::Struct(struct_def) => visitor.visit_struct_def(struct_def),
+        Item::Impl(impl_def) => visitor.visit_impl(impl_def),
+        Item::Enum(enum_def) => visitor.visit_enum_def(enum_def),
+    }
+}
+
+pub fn walk_func_def<V: Visitor>(visitor: &mut V, func_def: &FuncDef) {
+    visitor.visit_ident(&func_def.name);
+    for arg in &func_def.args {
+        visitor.visit_ident(arg);
+    }
+    visitor.visit_block(&func_def.body);
+}
+
+pub fn walk_global<V: Visitor>(visitor: &mut V, global: &Global) {
+    visitor.visit_ident(&global.name);
+    visitor.visit_expr(&global.value);
+}
+
+pub fn walk_struct_def<V: Visitor>(visitor: &mut V, struct_def: &StructDef) {
+    visitor.visit_ident(&struct_def.name);
+    for field in &struct_def.fields {
+        visitor.visit_ident(field);
+    }
+}
+
+pub fn walk_impl<V: Visitor>(visitor: &mut V, impl_def: &Impl) {
+    visitor.visit_ident(&impl_def.name);
+    for method in &impl_def.methods {
+        visitor.visit_func_def(method);
+    }
+}
+
+pub fn walk_enum_def<V: Visitor>(visitor: &mut V, enum_def: &EnumDef) {
+    visitor.visit_ident(&enum_def.name);
+    for Variant { name, fields, .. } in &enum_def.variants {
+        visitor.visit_ident(name);
+        for field in fields {
+            visitor.visit_ident(field);
+        }
+    }
+}
+
+pub fn walk_block<V: Visitor>(visitor: &mut V, block: &Block) {
+    for stmt in block {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor>(visitor: &mut V, stmt: &Stmt) {
+    match &stmt.kind {
+        StmtKind::Expr(expr) => visitor.visit_expr(expr),
+        StmtKind::Assign(name, value) => {
+            visitor.visit_ident(name);
+            visitor.visit_expr(value);
+        }
+        StmtKind::SetIndex(target, index, value) => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(index);
+            visitor.visit_expr(value);
+        }
+        StmtKind::SetField(target, field, value) => {
+            visitor.visit_expr(target);
+            visitor.visit_ident(field);
+            visitor.visit_expr(value);
+        }
+        StmtKind::While(cond, body) => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(body);
+        }
+        StmtKind::For(name, iterable, body) => {
+            visitor.visit_ident(name);
+            visitor.visit_expr(iterable);
+            visitor.visit_block(body);
+        }
+        StmtKind::If(cond, then, otherwise) => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_block(otherwise);
+            }
+        }
+        StmtKind::Break | StmtKind::Continue => {}
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+    }
+}
+
+pub fn walk_expr<V: Visitor>(visitor: &mut V, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Literal(literal) => visitor.visit_literal(literal),
+        ExprKind::Ident(name) => visitor.visit_ident(name),
+        ExprKind::Call(callee, args) => {
+            visitor.visit_expr(callee);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::Index(target, index) => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(index);
+        }
+        ExprKind::List(items) => {
+            for item in items {
+                visitor.visit_expr(item);
+            }
+        }
+        ExprKind::Map(entries) => {
+            for (key, value) in entries {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        }
+        ExprKind::Field(target, field) => {
+            visitor.visit_expr(target);
+            visitor.visit_ident(field);
+        }
+        ExprKind::Struct(name, fields) => {
+            visitor.visit_ident(name);
+            for (field, value) in fields {
+                visitor.visit_ident(field);
+                visitor.visit_expr(value);
+            }
+        }
+        ExprKind::Match(value, arms) => {
+            visitor.visit_expr(value);
+            for arm in arms {
+                visitor.visit_pattern(&arm.pattern);
+                visitor.visit_expr(&arm.body);
+            }
+        }
+        ExprKind::Lambda(args, body) => {
+            for arg in args {
+                visitor.visit_ident(arg);
+            }
+            visitor.visit_block(body);
+        }
+        ExprKind::Unary(_, operand) => visitor.visit_expr(operand),
+        ExprKind::Binary(_, lhs, rhs) => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        ExprKind::Range(start, end) => {
+            visitor.visit_expr(start);
+            visitor.visit_expr(end);
+        }
+    }
+}
+
+pub fn walk_pattern<V: Visitor>(visitor: &mut V, pattern: &Pattern) {
+    match &pattern.kind {
+        PatternKind::Literal(literal) => visitor.visit_literal(literal),
+        PatternKind::Wildcard => {}
+        PatternKind::Binding(name) => visitor.visit_ident(name),
+        PatternKind::Variant(name, fields) => {
+            visitor.visit_ident(name);
+            for field in fields {
+                visitor.visit_pattern(field);
+            }
+        }
+    }
+}
+
+/// Traversal that edits the tree in place.
+pub trait VisitorMut: Sized {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        walk_item_mut(self, item)
+    }
+
+    fn visit_func_def_mut(&mut self, func_def: &mut FuncDef) {
+        walk_func_def_mut(self, func_def)
+    }
+
+    fn visit_global_mut(&mut self, global: &mut Global) {
+        walk_global_mut(self, global)
+    }
+
+    fn visit_struct_def_mut(&mut self, struct_def: &mut StructDef) {
+        walk_struct_def_mut(self, struct_def)
+    }
+
+    fn visit_impl_mut(&mut self, impl_def: &mut Impl) {
+        walk_impl_mut(self, impl_def)
+    }
+
+    fn visit_enum_def_mut(&mut self, enum_def: &mut EnumDef) {
+        walk_enum_def_mut(self, enum_def)
+    }
+
+    fn visit_ident_mut(&mut self, _ident: &mut Ident) {}
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        walk_pattern_mut(self, pattern)
+    }
+
+    fn visit_literal_mut(&mut self, _literal: &mut Literal) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut>(visitor: &mut V, program: &mut Program) {
+    for item in program {
+        visitor.visit_item_mut(item);
+    }
+}
+
+pub fn walk_item_mut<V: VisitorMut>(visitor: &mut V, item: &mut Item) {
+    match item {
+        Item::FuncDef(func_def) => visitor.visit_func_def_mut(func_def),
+        Item::Global(global) => visitor.visit_global_mut(global),
+        Item::Struct(struct_def) => visitor.visit_struct_def_mut(struct_def),
+        Item::Impl(impl_def) => visitor.visit_impl_mut(impl_def),
+        Item::Enum(enum_def) => visitor.visit_enum_def_mut(enum_def),
+    }
+}
+
+pub fn walk_func_def_mut<V: VisitorMut>(visitor: &mut V, func_def: &mut FuncDef) {
+    visitor.visit_ident_mut(&mut func_def.name);
+    for arg in &mut func_def.args {
+        visitor.visit_ident_mut(arg);
+    }
+    visitor.visit_block_mut(&mut func_def.body);
+}
+
+pub fn walk_global_mut<V: VisitorMut>(visitor: &mut V, global: &mut Global) {
+    visitor.visit_ident_mut(&mut global.name);
+    visitor.visit_expr_mut(&mut global.value);
+}
+
+pub fn walk_struct_def_mut<V: VisitorMut>(visitor: &mut V, struct_def: &mut StructDef) {
+    visitor.visit_ident_mut(&mut struct_def.name);
+    for field in &mut struct_def.fields {
+        visitor.visit_ident_mut(field);
+    }
+}
+
+pub fn walk_impl_mut<V: VisitorMut>(visitor: &mut V, impl_def: &mut Impl) {
+    visitor.visit_ident_mut(&mut impl_def.name);
+    for method in &mut impl_def.methods {
+        visitor.visit_func_def_mut(method);
+    }
+}
+
+pub fn walk_enum_def_mut<V: VisitorMut>(visitor: &mut V, enum_def: &mut EnumDef) {
+    visitor.visit_ident_mut(&mut enum_def.name);
+    for Variant { name, fields, .. } in &mut enum_def.variants {
+        visitor.visit_ident_mut(name);
+        for field in fields {
+            visitor.visit_ident_mut(field);
+        }
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut>(visitor: &mut V, block: &mut Block) {
+    for stmt in block {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut>(visitor: &mut V, stmt: &mut Stmt) {
+    match &mut stmt.kind {
+        StmtKind::Expr(expr) => visitor.visit_expr_mut(expr),
+        StmtKind::Assign(name, value) => {
+            visitor.visit_ident_mut(name);
+            visitor.visit_expr_mut(value);
+        }
+        StmtKind::SetIndex(target, index, value) => {
+            visitor.visit_expr_mut(target);
+            visitor.visit_expr_mut(index);
+            visitor.visit_expr_mut(value);
+        }
+        StmtKind::SetField(target, field, value) => {
+            visitor.visit_expr_mut(target);
+            visitor.visit_ident_mut(field);
+            visitor.visit_expr_mut(value);
+        }
+        StmtKind::While(cond, body) => {
+            visitor.visit_expr_mut(cond);
+            visitor.visit_block_mut(body);
+        }
+        StmtKind::For(name, iterable, body) => {
+            visitor.visit_ident_mut(name);
+            visitor.visit_expr_mut(iterable);
+            visitor.visit_block_mut(body);
+        }
+        StmtKind::If(cond, then, otherwise) => {
+            visitor.visit_expr_mut(cond);
+            visitor.visit_block_mut(then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_block_mut(otherwise);
+            }
+        }
+        StmtKind::Break | StmtKind::Continue => {}
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut>(visitor: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Literal(literal) => visitor.visit_literal_mut(literal),
+        ExprKind::Ident(name) => visitor.visit_ident_mut(name),
+        ExprKind::Call(callee, args) => {
+            visitor.visit_expr_mut(callee);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        ExprKind::Index(target, index) => {
+            visitor.visit_expr_mut(target);
+            visitor.visit_expr_mut(index);
+        }
+        ExprKind::List(items) => {
+            for item in items {
+                visitor.visit_expr_mut(item);
+            }
+        }
+        ExprKind::Map(entries) => {
+            for (key, value) in entries {
+                visitor.visit_expr_mut(key);
+                visitor.visit_expr_mut(value);
+            }
+        }
+        ExprKind::Field(target, field) => {
+            visitor.visit_expr_mut(target);
+            visitor.visit_ident_mut(field);
+        }
+        ExprKind::Struct(name, fields) => {
+            visitor.visit_ident_mut(name);
+            for (field, value) in fields {
+                visitor.visit_ident_mut(field);
+                visitor.visit_expr_mut(value);
+            }
+        }
+        ExprKind::Match(value, arms) => {
+            visitor.visit_expr_mut(value);
+            for arm in arms {
+                visitor.visit_pattern_mut(&mut arm.pattern);
+                visitor.visit_expr_mut(&mut arm.body);
+            }
+        }
+        ExprKind::Lambda(args, body) => {
+            for arg in args {
+                visitor.visit_ident_mut(arg);
+            }
+            visitor.visit_block_mut(body);
+        }
+        ExprKind::Unary(_, operand) => visitor.visit_expr_mut(operand),
+        ExprKind::Binary(_, lhs, rhs) => {
+            visitor.visit_expr_mut(lhs);
+            visitor.visit_expr_mut(rhs);
+        }
+        ExprKind::Range(start, end) => {
+            visitor.visit_expr_mut(start);
+            visitor.visit_expr_mut(end);
+        }
+    }
+}
+
+pub fn walk_pattern_mut<V: VisitorMut>(visitor: &mut V, pattern: &mut Pattern) {
+    match &mut pattern.kind {
+        PatternKind::Literal(literal) => visitor.visit_literal_mut(literal),
+        PatternKind::Wildcard => {}
+        PatternKind::Binding(name) => visitor.visit_ident_mut(name),
+        PatternKind::Variant(name, fields) => {
+            visitor.visit_ident_mut(name);
+            for field in fields {
+                visitor.visit_pattern_mut(field);
+            }
+        }
+    }
+}
+
+/// Traversal that consumes the tree and builds a new one, for passes that
+/// replace a node with a different kind of node.
+pub trait Fold: Sized {
+    fn fold_program(&mut self, program: Program) -> Program {
+        walk_program_fold(self, program)
+    }
+
+    fn fold_item(&mut self, item: Item) -> Item {
+        walk_item_fold(self, item)
+    }
+
+    fn fold_func_def(&mut self, func_def: FuncDef) -> FuncDef {
+        walk_func_def_fold(self, func_def)
+    }
+
+    fn fold_global(&mut self, global: Global) -> Global {
+        walk_global_fold(self, global)
+    }
+
+    fn fold_struct_def(&mut self, struct_def: StructDef) -> StructDef {
+        walk_struct_def_fold(self, struct_def)
+    }
+
+    fn fold_impl(&mut self, impl_def: Impl) -> Impl {
+        walk_impl_fold(self, impl_def)
+    }
+
+    fn fold_enum_def(&mut self, enum_def: EnumDef) -> EnumDef {
+        walk_enum_def_fold(self, enum_def)
+    }
+
+    fn fold_ident(&mut self, ident: Ident) -> Ident {
+        ident
+    }
+
+    fn fold_block(&mut self, block: Block) -> Block {
+        walk_block_fold(self, block)
+    }
+
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        walk_stmt_fold(self, stmt)
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        walk_expr_fold(self, expr)
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        walk_pattern_fold(self, pattern)
+    }
+
+    fn fold_literal(&mut self, literal: Literal) -> Literal {
+        literal
+    }
+}
+
+pub fn walk_program_fold<F: Fold>(folder: &mut F, program: Program) -> Program {
+    program.into_iter().map(|item| folder.fold_item(item)).collect()
+}
+
+pub fn walk_item_fold<F: Fold>(folder: &mut F, item: Item) -> Item {
+    match item {
+        Item::FuncDef(func_def) => Item::FuncDef(folder.fold_func_def(func_def)),
+        Item::Global(global) => Item::Global(folder.fold_global(global)),
+        Item::Struct(struct_def) => Item::Struct(folder.fold_struct_def(struct_def)),
+        Item::Impl(impl_def) => Item::Impl(folder.fold_impl(impl_def)),
+        Item::Enum(enum_def) => Item::Enum(folder.fold_enum_def(enum_def)),
+    }
+}
+
+pub fn walk_func_def_fold<F: Fold>(folder: &mut F, func_def: FuncDef) -> FuncDef {
+    FuncDef {
+        name: folder.fold_ident(func_def.name),
+        args: func_def.args.into_iter().map(|arg| folder.fold_ident(arg)).collect(),
+        body: folder.fold_block(func_def.body),
+        ..func_def
+    }
+}
+
+pub fn walk_global_fold<F: Fold>(folder: &mut F, global: Global) -> Global {
+    Global { name: folder.fold_ident(global.name), value: folder.fold_expr(global.value), ..global }
+}
+
+pub fn walk_struct_def_fold<F: Fold>(folder: &mut F, struct_def: StructDef) -> StructDef {
+    StructDef {
+        name: folder.fold_ident(struct_def.name),
+        fields: struct_def.fields.into_iter().map(|field| folder.fold_ident(field)).collect(),
+        ..struct_def
+    }
+}
+
+pub fn walk_impl_fold<F: Fold>(folder: &mut F, impl_def: Impl) -> Impl {
+    Impl {
+        name: folder.fold_ident(impl_def.name),
+        methods: impl_def.methods.into_iter().map(|method| folder.fold_func_def(method)).collect(),
+        ..impl_def
+    }
+}
+
+pub fn walk_enum_def_fold<F: Fold>(folder: &mut F, enum_def: EnumDef) -> EnumDef {
+    let name = folder.fold_ident(enum_def.name);
+    let variants = enum_def.variants.into_iter().map(|variant| Variant {
+        name: folder.fold_ident(variant.name),
+        fields: variant.fields.into_iter().map(|field| folder.fold_ident(field)).collect(),
+        ..variant
+    });
+
+    EnumDef { name, variants: variants.collect(), ..enum_def }
+}
+
+pub fn walk_block_fold<F: Fold>(folder: &mut F, block: Block) -> Block {
+    block.into_iter().map(|stmt| folder.fold_stmt(stmt)).collect()
+}
+
+pub fn walk_stmt_fold<F: Fold>(folder: &mut F, stmt: Stmt) -> Stmt {
+    let kind = match stmt.kind {
+        StmtKind::Expr(expr) => StmtKind::Expr(folder.fold_expr(expr)),
+        StmtKind::Assign(name, value) => StmtKind::Assign(folder.fold_ident(name), folder.fold_expr(value)),
+        StmtKind::SetIndex(target, index, value) => {
+            StmtKind::SetIndex(Box::new(folder.fold_expr(*target)), Box::new(folder.fold_expr(*index)), folder.fold_expr(value))
+        }
+        StmtKind::SetField(target, field, value) => {
+            StmtKind::SetField(Box::new(folder.fold_expr(*target)), folder.fold_ident(field), folder.fold_expr(value))
+        }
+        StmtKind::While(cond, body) => StmtKind::While(folder.fold_expr(cond), folder.fold_block(body)),
+        StmtKind::For(name, iterable, body) => {
+            StmtKind::For(folder.fold_ident(name), folder.fold_expr(iterable), folder.fold_block(body))
+        }
+        StmtKind::If(cond, then, otherwise) => StmtKind::If(
+            folder.fold_expr(cond),
+            folder.fold_block(then),
+            otherwise.map(|otherwise| folder.fold_block(otherwise)),
+        ),
+        StmtKind::Break => StmtKind::Break,
+        StmtKind::Continue => StmtKind::Continue,
+        StmtKind::Return(value) => StmtKind::Return(value.map(|value| folder.fold_expr(value))),
+    };
+
+    Stmt { kind, span: stmt.span }
+}
+
+pub fn walk_expr_fold<F: Fold>(folder: &mut F, expr: Expr) -> Expr {
+    let kind = match expr.kind {
+        ExprKind::Literal(literal) => ExprKind::Literal(folder.fold_literal(literal)),
+        ExprKind::Ident(name) => ExprKind::Ident(folder.fold_ident(name)),
+        ExprKind::Call(callee, args) => ExprKind::Call(
+            Box::new(folder.fold_expr(*callee)),
+            args.into_iter().map(|arg| folder.fold_expr(arg)).collect(),
+        ),
+        ExprKind::Index(target, index) => {
+            ExprKind::Index(Box::new(folder.fold_expr(*target)), Box::new(folder.fold_expr(*index)))
+        }
+        ExprKind::List(items) => ExprKind::List(items.into_iter().map(|item| folder.fold_expr(item)).collect()),
+        ExprKind::Map(entries) => ExprKind::Map(
+            entries.into_iter().map(|(key, value)| (folder.fold_expr(key), folder.fold_expr(value))).collect(),
+        ),
+        ExprKind::Field(target, field) => ExprKind::Field(Box::new(folder.fold_expr(*target)), folder.fold_ident(field)),
+        ExprKind::Struct(name, fields) => ExprKind::Struct(
+            folder.fold_ident(name),
+            fields.into_iter().map(|(field, value)| (folder.fold_ident(field), folder.fold_expr(value))).collect(),
+        ),
+        ExprKind::Match(value, arms) => ExprKind::Match(
+            Box::new(folder.fold_expr(*value)),
+            arms.into_iter().map(|arm| Arm { pattern: folder.fold_pattern(arm.pattern), body: folder.fold_expr(arm.body), ..arm }).collect(),
+        ),
+        ExprKind::Lambda(args, body) => ExprKind::Lambda(
+            args.into_iter().map(|arg| folder.fold_ident(arg)).collect(),
+            folder.fold_block(body),
+        ),
+        ExprKind::Unary(op, operand) => ExprKind::Unary(op, Box::new(folder.fold_expr(*operand))),
+        ExprKind::Binary(op, lhs, rhs) => {
+            ExprKind::Binary(op, Box::new(folder.fold_expr(*lhs)), Box::new(folder.fold_expr(*rhs)))
+        }
+        ExprKind::Range(start, end) => {
+            ExprKind::Range(Box::new(folder.fold_expr(*start)), Box::new(folder.fold_expr(*end)))
+        }
+    };
+
+    Expr { kind, span: expr.span }
+}
+
+pub fn walk_pattern_fold<F: Fold>(folder: &mut F, pattern: Pattern) -> Pattern {
+    let kind = match pattern.kind {
+        PatternKind::Literal(literal) => PatternKind::Literal(folder.fold_literal(literal)),
+        PatternKind::Wildcard => PatternKind::Wildcard,
+        PatternKind::Binding(name) => PatternKind::Binding(folder.fold_ident(name)),
+        PatternKind::Variant(name, fields) => {
+            PatternKind::Variant(folder.fold_ident(name), fields.into_iter().map(|field| folder.fold_pattern(field)).collect())
+        }
+    };
+
+    Pattern { kind, ..pattern }
+}
+
+#[cfg(test)]
+mod tests {
+use super::*;
+use super::super::parser::BinOp;
+use super::super::{parser, printer, scanner};
+
+    fn parse(text: &str) -> Program {
+        parser::Parser::new(scanner::Scanner::new(text)).parse().unwrap()
+    }
+
+    const TEXT: &str = r#"
+        fn main() {
+            total = 0;
+            while total < 2 * 3 {
+                if total % 2 == 0 { print(total + 1); } else { total = total + 1; }
+                total = total + 1;
+            }
+            return sum(total, 4 * 5);
+        }
+
+        fn sum(a, b) {
+            return a + b;
+        }
+
+        const STEP = 1 + 1;
+    "#;
+
+    /// Names every identifier, whether declared or used.
+    #[derive(Default)]
+    struct Names(Vec<String>);
+
+    impl Visitor for Names {
+        fn visit_ident(&mut self, ident: &Ident) {
+            self.0.push(ident.name.clone());
+        }
+    }
+
+    #[test]
+    fn visitor_reaches_every_node() {
+        let mut names = Names::default();
+        names.visit_program(&parse(TEXT));
+
+        assert_eq!(names.0, [
+            "main", "total", "total", "total", "print", "total", "total", "total",
+            "total", "total", "sum", "total", "sum", "a", "b", "a", "b", "STEP",
+        ]);
+    }
+
+    struct Rename<'a>(&'a str, &'a str);
+
+    impl VisitorMut for Rename<'_> {
+        fn visit_ident_mut(&mut self, ident: &mut Ident) {
+            if ident.name == self.0 {
+                ident.name = self.1.to_string();
+            }
+        }
+    }
+
+    #[test]
+    fn visitor_mut_edits_in_place() {
+        let mut program = parse("fn f(x) { x = x + 1; return g(x); }");
+        Rename("x", "y").visit_program_mut(&mut program);
+
+        assert_eq!(printer::print(&program), "fn f(y) {\n    y = y + 1;\n    return g(y);\n}\n");
+    }
+
+    /// Where `.0` is declared or used, as `(line, column)`.
+    struct Occurrences<'a>(&'a str, Vec<(usize, usize)>);
+
+    impl Visitor for Occurrences<'_> {
+        fn visit_ident(&mut self, ident: &Ident) {
+            if ident.name == self.0 {
+                self.1.push((ident.span.line, ident.span.column));
+            }
+        }
+    }
+
+    /// Renames `.0` to `.1` while rebuilding the tree.
+    struct RenameFold<'a>(&'a str, &'a str);
+
+    impl Fold for RenameFold<'_> {
+        fn fold_ident(&mut self, ident: Ident) -> Ident {
+            if ident.name == self.0 {
+                return Ident { name: self.1.to_string(), ..ident };
+            }
+            ident
+        }
+    }
+
+    #[test]
+    fn idents_are_visited_where_declared_and_used() {
+        let text = "fn f(x) {\n    x = x + 1;\n    return fn(y) { return g(x, y); };\n}";
+        let mut occurrences = Occurrences("x", Vec::new());
+        occurrences.visit_program(&parse(text));
+        assert_eq!(occurrences.1, [(1, 6), (2, 5), (2, 9), (3, 29)]);
+
+        let program = RenameFold("x", "z").fold_program(parse(text));
+        assert_eq!(printer::print(&program), "fn f(z) {\n    z = z + 1;\n    return fn(y) {\n        return g(z, y);\n    };\n}\n");
+    }
+
+    /// Evaluates integer arithmetic on literals ahead of time.
+    struct ConstantFolding;
+
+    impl Fold for ConstantFolding {
+        fn fold_expr(&mut self, expr: Expr) -> Expr {
+            let expr = walk_expr_fold(self, expr);
+            let ExprKind::Binary(op, lhs, rhs) = &expr.kind else { return expr };
+            let (ExprKind::Literal(Literal::Int(lhs)), ExprKind::Literal(Literal::Int(rhs))) = (&lhs.kind, &rhs.kind) else {
+                return expr;
+            };
+
+            let value = match op {
+                BinOp::Add => lhs.checked_add(*rhs),
+                BinOp::Mul => lhs.checked_mul(*rhs),
+                _ => None,
+            };
+            match value {
+                Some(value) => Expr { kind: ExprKind::Literal(Literal::Int(value)), span: expr.span },
+                None => expr,
+            }
+        }
+    }
+
+    #[test]
+    fn fold_rebuilds_the_tree() {
+        let program = ConstantFolding.fold_program(parse(TEXT));
+        let printed = printer::print(&program);
+
+        assert!(printed.contains("while total < 6 {"), "{}", printed);
+        assert!(printed.contains("return sum(total, 20);"), "{}", printed);
+        assert!(printed.contains("total = total + 1;"), "{}", printed);
+        assert!(printed.ends_with("const STEP = 2;\n"), "{}", printed);
+    }
+}